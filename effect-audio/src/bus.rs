@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+/// A group that tracks and effects are routed into. Buses form a tree under
+/// `BusID::MASTER`, and each bus's volume is multiplied by those of its parents.
+#[derive(Hash, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct BusID(pub &'static str);
+
+impl BusID {
+    pub const MASTER: BusID = BusID("Master");
+    pub const MUSIC: BusID = BusID("Music");
+    pub const SFX: BusID = BusID("SFX");
+    pub const VOICE: BusID = BusID("Voice");
+    pub const UI: BusID = BusID("UI");
+}
+
+pub struct AudioBus {
+    pub(crate) parent: Option<BusID>,
+    pub(crate) volume: f32,
+    pub(crate) muted: bool,
+    pub(crate) soloed: bool,
+    pub(crate) paused: bool,
+}
+
+impl AudioBus {
+    pub fn new(parent: Option<BusID>) -> Self {
+        let volume = 1.0;
+        let muted = false;
+        let soloed = false;
+        let paused = false;
+        Self {
+            parent,
+            volume,
+            muted,
+            soloed,
+            paused,
+        }
+    }
+
+    pub fn parent(&self) -> Option<BusID> {
+        self.parent
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn is_soloed(&self) -> bool {
+        self.soloed
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
+
+pub struct AudioBusSystem;
+
+impl AudioBusSystem {
+    /// Master with Music, SFX, Voice and UI beneath it.
+    pub fn default_buses() -> HashMap<BusID, AudioBus> {
+        let mut buses = HashMap::new();
+        buses.insert(BusID::MASTER, AudioBus::new(None));
+        for id in [BusID::MUSIC, BusID::SFX, BusID::VOICE, BusID::UI] {
+            buses.insert(id, AudioBus::new(Some(BusID::MASTER)));
+        }
+        buses
+    }
+
+    /// The volume a bus plays at after its parents' volumes, mutes and any solo,
+    /// and whether it or any parent is paused. While any bus is soloed, only buses
+    /// that are soloed or beneath a soloed bus are heard.
+    pub fn output(buses: &HashMap<BusID, AudioBus>, id: BusID) -> (f32, bool) {
+        let any_soloed = buses.values().any(|bus| bus.soloed);
+        let mut gain = 1.0;
+        let mut paused = false;
+        let mut soloed = false;
+        let mut current = Some(id);
+        while let Some(bus) = current.and_then(|id| buses.get(&id)) {
+            gain *= if bus.muted { 0.0 } else { bus.volume };
+            paused |= bus.paused;
+            soloed |= bus.soloed;
+            current = bus.parent;
+        }
+        if any_soloed && !soloed {
+            gain = 0.0;
+        }
+        (gain, paused)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AMBIENCE: BusID = BusID("Ambience");
+
+    // Master > SFX > Ambience, alongside the other default buses
+    fn buses() -> HashMap<BusID, AudioBus> {
+        let mut buses = AudioBusSystem::default_buses();
+        buses.insert(AMBIENCE, AudioBus::new(Some(BusID::SFX)));
+        buses
+    }
+
+    fn gain(buses: &HashMap<BusID, AudioBus>, id: BusID) -> f32 {
+        AudioBusSystem::output(buses, id).0
+    }
+
+    #[test]
+    fn volumes_multiply_down_the_tree() {
+        let mut buses = buses();
+        buses.get_mut(&BusID::MASTER).unwrap().volume = 0.5;
+        buses.get_mut(&BusID::SFX).unwrap().volume = 0.5;
+        buses.get_mut(&AMBIENCE).unwrap().volume = 0.5;
+        assert_eq!(gain(&buses, BusID::MASTER), 0.5);
+        assert_eq!(gain(&buses, BusID::SFX), 0.25);
+        assert_eq!(gain(&buses, AMBIENCE), 0.125);
+        assert_eq!(gain(&buses, BusID::MUSIC), 0.5);
+    }
+
+    #[test]
+    fn muting_silences_the_bus_and_everything_beneath() {
+        let mut buses = buses();
+        buses.get_mut(&BusID::SFX).unwrap().muted = true;
+        assert_eq!(gain(&buses, BusID::SFX), 0.0);
+        assert_eq!(gain(&buses, AMBIENCE), 0.0);
+        assert_eq!(gain(&buses, BusID::MUSIC), 1.0);
+    }
+
+    #[test]
+    fn solo_silences_buses_outside_it() {
+        let mut buses = buses();
+        buses.get_mut(&BusID::SFX).unwrap().soloed = true;
+        assert_eq!(gain(&buses, BusID::SFX), 1.0);
+        assert_eq!(gain(&buses, AMBIENCE), 1.0);
+        assert_eq!(gain(&buses, BusID::MUSIC), 0.0);
+        assert_eq!(gain(&buses, BusID::MASTER), 0.0);
+    }
+
+    #[test]
+    fn pausing_reaches_buses_beneath() {
+        let mut buses = buses();
+        buses.get_mut(&BusID::SFX).unwrap().paused = true;
+        assert!(AudioBusSystem::output(&buses, AMBIENCE).1);
+        assert!(!AudioBusSystem::output(&buses, BusID::MUSIC).1);
+        assert_eq!(gain(&buses, AMBIENCE), 1.0);
+    }
+}
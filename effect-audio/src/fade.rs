@@ -0,0 +1,166 @@
+use std::{f32::consts::FRAC_PI_2, time::Duration};
+
+// Exponential fades treat anything quieter than -60dB as silence
+const SILENCE_DB: f32 = -60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FadeCurve {
+    /// Even steps in volume, sounds like it drops off suddenly at the end of a fade out.
+    Linear,
+    /// Keeps the combined loudness of a crossfade steady, with no dip in the middle.
+    EqualPower,
+    /// Even steps in decibels, which sound even to the ear.
+    Exponential,
+}
+
+/// What happens to a track once its fade has finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FadeEnd {
+    Continue,
+    /// The track is paused and its volume restored, ready to be played again.
+    Pause,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Fade {
+    from: f32,
+    to: f32,
+    duration: Duration,
+    elapsed: Duration,
+    curve: FadeCurve,
+    end: FadeEnd,
+}
+
+impl Fade {
+    pub fn new(from: f32, to: f32, duration: Duration, curve: FadeCurve, end: FadeEnd) -> Self {
+        let elapsed = Duration::ZERO;
+        Self {
+            from,
+            to,
+            duration,
+            elapsed,
+            curve,
+            end,
+        }
+    }
+
+    pub fn end(&self) -> FadeEnd {
+        self.end
+    }
+
+    pub fn finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+pub struct FadeSystem;
+
+impl FadeSystem {
+    pub fn advance(fade: &mut Fade, delta_time: Duration) {
+        fade.elapsed = (fade.elapsed + delta_time).min(fade.duration);
+    }
+
+    /// The volume multiplier at the fade's current point.
+    pub fn gain(fade: &Fade) -> f32 {
+        if fade.finished() {
+            return fade.to;
+        }
+        let t = fade.elapsed.as_secs_f32() / fade.duration.as_secs_f32();
+        match fade.curve {
+            FadeCurve::Linear => fade.from + (fade.to - fade.from) * t,
+            // Rising follows sine and falling follows cosine, so a crossfade's
+            // squared gains always add up to one
+            FadeCurve::EqualPower => {
+                if fade.to >= fade.from {
+                    fade.from + (fade.to - fade.from) * (t * FRAC_PI_2).sin()
+                } else {
+                    fade.to + (fade.from - fade.to) * (t * FRAC_PI_2).cos()
+                }
+            }
+            FadeCurve::Exponential => {
+                let from = Self::to_db(fade.from);
+                let to = Self::to_db(fade.to);
+                10f32.powf((from + (to - from) * t) / 20.0)
+            }
+        }
+    }
+
+    fn to_db(gain: f32) -> f32 {
+        if gain <= 0.0 {
+            SILENCE_DB
+        } else {
+            (20.0 * gain.log10()).max(SILENCE_DB)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn gain_at(from: f32, to: f32, curve: FadeCurve, elapsed: Duration) -> f32 {
+        let mut fade = Fade::new(from, to, SECOND, curve, FadeEnd::Continue);
+        FadeSystem::advance(&mut fade, elapsed);
+        FadeSystem::gain(&fade)
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn every_curve_runs_from_start_to_end() {
+        for curve in [
+            FadeCurve::Linear,
+            FadeCurve::EqualPower,
+            FadeCurve::Exponential,
+        ] {
+            assert!(close(gain_at(1.0, 0.5, curve, Duration::ZERO), 1.0));
+            assert_eq!(gain_at(1.0, 0.5, curve, SECOND), 0.5);
+            assert_eq!(gain_at(0.5, 1.0, curve, SECOND), 1.0);
+        }
+    }
+
+    #[test]
+    fn linear_moves_in_even_steps() {
+        let half = SECOND / 2;
+        assert!(close(gain_at(0.0, 1.0, FadeCurve::Linear, half), 0.5));
+        assert!(close(gain_at(1.0, 0.0, FadeCurve::Linear, half), 0.5));
+        assert!(close(
+            gain_at(0.0, 1.0, FadeCurve::Linear, SECOND / 4),
+            0.25
+        ));
+    }
+
+    #[test]
+    fn equal_power_crossfade_keeps_loudness_steady() {
+        for quarter in 0..=4 {
+            let elapsed = SECOND * quarter / 4;
+            let rising = gain_at(0.0, 1.0, FadeCurve::EqualPower, elapsed);
+            let falling = gain_at(1.0, 0.0, FadeCurve::EqualPower, elapsed);
+            assert!(close(rising * rising + falling * falling, 1.0));
+        }
+    }
+
+    #[test]
+    fn exponential_moves_in_even_decibel_steps() {
+        // Half way from 0dB to -60dB is -30dB
+        let half = gain_at(1.0, 0.0, FadeCurve::Exponential, SECOND / 2);
+        assert!(close(half, 10f32.powf(-30.0 / 20.0)));
+        let quarter = gain_at(1.0, 0.1, FadeCurve::Exponential, SECOND / 4);
+        assert!(close(quarter, 10f32.powf(-5.0 / 20.0)));
+    }
+
+    #[test]
+    fn advance_stops_at_the_end() {
+        let mut fade = Fade::new(1.0, 0.0, SECOND, FadeCurve::Linear, FadeEnd::Pause);
+        FadeSystem::advance(&mut fade, SECOND / 2);
+        assert!(!fade.finished());
+        FadeSystem::advance(&mut fade, SECOND);
+        assert!(fade.finished());
+        assert_eq!(FadeSystem::gain(&fade), 0.0);
+        assert_eq!(fade.end(), FadeEnd::Pause);
+    }
+}
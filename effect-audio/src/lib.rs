@@ -1,2 +1,6 @@
+pub mod bus;
+pub mod fade;
 pub mod mixer;
+pub mod output;
 pub mod spatial;
+pub mod stream;
@@ -1,28 +1,88 @@
 use anyhow::Result;
 use effect_util::effect_error::EffectError;
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
-use std::{collections::HashMap, fs::*, io::*, time::Duration};
+use rodio::{Decoder, Sink, Source};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fs::*,
+    io::*,
+    rc::Weak,
+    time::Duration,
+};
+
+use crate::bus::{AudioBus, AudioBusSystem, BusID};
+use crate::fade::{Fade, FadeCurve, FadeEnd, FadeSystem};
+use crate::output::{AudioOutput, AudioOutputSystem};
+use crate::spatial::SpatialSink;
+use crate::stream::{StreamedSource, DEFAULT_BUFFERED_CHUNKS};
 
 #[derive(Hash, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct AudioID(pub &'static str);
 
+/// Where a track's audio is decoded from.
+pub enum AudioData {
+    /// The whole file, read up front.
+    Memory(Cursor<Vec<u8>>),
+    /// Decoded from disk as it plays, keeping at most `buffered_chunks` in memory.
+    Stream {
+        path: &'static str,
+        buffered_chunks: usize,
+    },
+}
+
 pub struct AudioTrack {
     sink: Option<Sink>,
-    data: Cursor<Vec<u8>>,
-    _stream: OutputStream,
-    stream_handle: OutputStreamHandle,
+    data: AudioData,
+    bus: BusID,
+    // Cells so tracks can be played, paused and turned up through a shared `Mixer`
+    volume: Cell<f32>,
+    paused: Cell<bool>,
+    fade_gain: Cell<f32>,
+    fade: Cell<Option<Fade>>,
+}
+
+// An effect that is still playing, kept so bus changes reach it
+struct PlayingEffect {
+    sink: Sink,
+    bus: BusID,
+    volume: f32,
 }
 
 pub struct Mixer {
+    output: AudioOutput,
+    buses: HashMap<BusID, AudioBus>,
     tracks: HashMap<AudioID, AudioTrack>,
     effects: HashMap<AudioID, AudioTrack>,
+    playing_effects: RefCell<Vec<PlayingEffect>>,
+    // Spatial tracks are owned by the app, dropped ones are skipped and removed
+    spatial_tracks: RefCell<Vec<Weak<SpatialSink>>>,
 }
 
 impl Mixer {
+    /// Plays through the default device, or silently when there is none.
     pub fn new() -> Self {
+        Self::with_output(AudioOutput::default_or_null())
+    }
+
+    pub fn with_output(output: AudioOutput) -> Self {
+        let buses = AudioBusSystem::default_buses();
         let tracks = HashMap::new();
         let effects = HashMap::new();
-        Self { tracks, effects }
+        let playing_effects = RefCell::new(Vec::new());
+        let spatial_tracks = RefCell::new(Vec::new());
+        Self {
+            output,
+            buses,
+            tracks,
+            effects,
+            playing_effects,
+            spatial_tracks,
+        }
+    }
+
+    /// The output shared by everything in the mixer, spatial audio included.
+    pub fn output(&self) -> &AudioOutput {
+        &self.output
     }
 
     pub fn get_tracks(&self) -> Vec<&AudioID> {
@@ -33,12 +93,41 @@ impl Mixer {
         self.effects.keys().collect()
     }
 
+    pub fn get_buses(&self) -> Vec<&BusID> {
+        self.buses.keys().collect()
+    }
+
+    pub(crate) fn buses(&self) -> &HashMap<BusID, AudioBus> {
+        &self.buses
+    }
+
+    pub fn bus(&self, id: BusID) -> Result<&AudioBus> {
+        self.buses
+            .get(&id)
+            .ok_or(EffectError::new("Bus not in mixer").into())
+    }
+
+    /// The volume the bus actually plays at, after its parents, mutes and solos.
+    pub fn bus_output_volume(&self, id: BusID) -> Result<f32> {
+        self.bus(id)?;
+        Ok(AudioBusSystem::output(&self.buses, id).0)
+    }
+
+    /// The track's own volume, before its bus is applied.
     pub fn track_volume(&self, id: AudioID) -> Result<f32> {
         let track = self
             .tracks
             .get(&id)
             .ok_or(EffectError::new("Track not in mixer"))?;
-        Ok(track.sink.as_ref().unwrap().volume())
+        Ok(track.volume.get())
+    }
+
+    pub fn track_bus(&self, id: AudioID) -> Result<BusID> {
+        let track = self
+            .tracks
+            .get(&id)
+            .ok_or(EffectError::new("Track not in mixer"))?;
+        Ok(track.bus)
     }
 
     pub fn track_speed(&self, id: AudioID) -> Result<f32> {
@@ -49,12 +138,19 @@ impl Mixer {
         Ok(track.sink.as_ref().unwrap().speed())
     }
 
+    /// Whether the track itself is paused, it is also silent while its bus is paused.
     pub fn is_paused(&self, id: AudioID) -> Result<bool> {
         let track = self
             .tracks
             .get(&id)
             .ok_or(EffectError::new("Track not in mixer"))?;
-        Ok(track.sink.as_ref().unwrap().is_paused())
+        Ok(track.paused.get())
+    }
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -62,6 +158,7 @@ pub struct MixerSystem;
 
 impl MixerSystem {
     pub fn create_sink(
+        output: &AudioOutput,
         path: &'static str,
         is_track: bool,
         repeat_infinite: bool,
@@ -70,35 +167,185 @@ impl MixerSystem {
         let mut file: Vec<u8> = Vec::new();
         File::open(path)?.read_to_end(&mut file)?;
         let cursor = Cursor::new(file);
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
+        MixerSystem::create_sink_from(
+            output,
+            AudioData::Memory(cursor),
+            is_track,
+            repeat_infinite,
+            starting_point,
+        )
+    }
+
+    /// Like `create_sink`, but decodes from disk while playing,
+    /// see `MixerSystem::add_streamed_track`.
+    pub fn create_streamed_sink(
+        output: &AudioOutput,
+        path: &'static str,
+        buffered_chunks: usize,
+        repeat_infinite: bool,
+        starting_point: Duration,
+    ) -> Result<AudioTrack> {
+        let data = AudioData::Stream {
+            path,
+            buffered_chunks,
+        };
+        MixerSystem::create_sink_from(output, data, true, repeat_infinite, starting_point)
+    }
+
+    fn create_sink_from(
+        output: &AudioOutput,
+        data: AudioData,
+        is_track: bool,
+        repeat_infinite: bool,
+        starting_point: Duration,
+    ) -> Result<AudioTrack> {
         let mut track = AudioTrack {
             sink: None,
-            _stream,
-            stream_handle,
-            data: cursor,
+            data,
+            bus: BusID::MASTER,
+            volume: Cell::new(1.0),
+            paused: Cell::new(true),
+            fade_gain: Cell::new(1.0),
+            fade: Cell::new(None),
         };
         if is_track {
-            let sink = Sink::try_new(&track.stream_handle).unwrap();
-            if repeat_infinite {
-                let source = Decoder::new(track.data.clone())
-                    .unwrap()
-                    .repeat_infinite()
-                    .skip_duration(starting_point);
-                sink.append(source);
-            } else {
-                let source = Decoder::new(track.data.clone())
-                    .unwrap()
-                    .skip_duration(starting_point);
-                sink.append(source);
-            }
+            let sink = AudioOutputSystem::create_sink(output)?;
+            MixerSystem::append_source(&sink, &track.data, repeat_infinite, starting_point)?;
             sink.pause();
             track.sink = Some(sink);
         }
         Ok(track)
     }
 
+    // Streams handle repeating and skipping themselves, as they can't be cloned
+    // for `repeat_infinite` and skipping ahead would decode on the audio thread
+    fn append_source(
+        sink: &Sink,
+        data: &AudioData,
+        repeat_infinite: bool,
+        starting_point: Duration,
+    ) -> Result<()> {
+        match data {
+            AudioData::Memory(cursor) => {
+                if repeat_infinite {
+                    let source = Decoder::new(cursor.clone())?
+                        .repeat_infinite()
+                        .skip_duration(starting_point);
+                    sink.append(source);
+                } else {
+                    let source = Decoder::new(cursor.clone())?.skip_duration(starting_point);
+                    sink.append(source);
+                }
+            }
+            AudioData::Stream {
+                path,
+                buffered_chunks,
+            } => {
+                let source =
+                    StreamedSource::new(path, starting_point, repeat_infinite, *buffered_chunks)?;
+                sink.append(source);
+            }
+        }
+        Ok(())
+    }
+
+    // Effects are always played from memory
+    fn effect_data(effect: &AudioTrack) -> Result<Cursor<Vec<u8>>> {
+        match &effect.data {
+            AudioData::Memory(cursor) => Ok(cursor.clone()),
+            AudioData::Stream { .. } => {
+                Err(EffectError::new("Effect is not loaded in memory").into())
+            }
+        }
+    }
+
+    // Sets the sink from the track's own state and its bus
+    fn apply_bus(buses: &HashMap<BusID, AudioBus>, track: &AudioTrack) {
+        let Some(sink) = track.sink.as_ref() else {
+            return;
+        };
+        let (gain, bus_paused) = AudioBusSystem::output(buses, track.bus);
+        sink.set_volume(track.volume.get() * track.fade_gain.get() * gain);
+        if track.paused.get() || bus_paused {
+            sink.pause();
+        } else {
+            sink.play();
+        }
+    }
+
+    fn apply_buses(mixer: &Mixer) {
+        for track in mixer.tracks.values() {
+            MixerSystem::apply_bus(&mixer.buses, track);
+        }
+        mixer
+            .spatial_tracks
+            .borrow_mut()
+            .retain(|track| match track.upgrade() {
+                Some(track) => {
+                    MixerSystem::apply_spatial_bus(&mixer.buses, &track);
+                    true
+                }
+                None => false,
+            });
+        let mut playing_effects = mixer.playing_effects.borrow_mut();
+        playing_effects.retain(|effect| !effect.sink.empty());
+        for effect in playing_effects.iter() {
+            let (gain, bus_paused) = AudioBusSystem::output(&mixer.buses, effect.bus);
+            effect.sink.set_volume(effect.volume * gain);
+            if bus_paused {
+                effect.sink.pause();
+            } else {
+                effect.sink.play();
+            }
+        }
+    }
+
+    // The same as `apply_bus`, for a spatial track
+    pub(crate) fn apply_spatial_bus(buses: &HashMap<BusID, AudioBus>, track: &SpatialSink) {
+        let (gain, bus_paused) = AudioBusSystem::output(buses, track.bus.get());
+        track.sink.set_volume(track.volume.get() * gain);
+        if track.paused.get() || bus_paused {
+            track.sink.pause();
+        } else {
+            track.sink.play();
+        }
+    }
+
+    pub(crate) fn add_spatial_track(mixer: &Mixer, track: Weak<SpatialSink>) {
+        mixer.spatial_tracks.borrow_mut().push(track);
+    }
+
+    /// Starts an effect's sink at its volume after the bus, and keeps it so bus
+    /// changes reach it. Returns `None` without playing while the bus is paused.
+    pub(crate) fn start_effect_sink(
+        mixer: &Mixer,
+        bus: BusID,
+        volume: f32,
+    ) -> Result<Option<Sink>> {
+        let (gain, bus_paused) = AudioBusSystem::output(&mixer.buses, bus);
+        if bus_paused {
+            return Ok(None);
+        }
+        let sink = AudioOutputSystem::create_sink(&mixer.output)?;
+        sink.set_volume(volume * gain);
+        Ok(Some(sink))
+    }
+
+    pub(crate) fn add_playing_effect(mixer: &Mixer, sink: Sink, bus: BusID, volume: f32) {
+        let mut playing_effects = mixer.playing_effects.borrow_mut();
+        playing_effects.retain(|effect| !effect.sink.empty());
+        playing_effects.push(PlayingEffect { sink, bus, volume });
+    }
+
+    fn insert_track(mixer: &mut Mixer, id: AudioID, mut track: AudioTrack, bus: BusID) {
+        track.bus = bus;
+        MixerSystem::apply_bus(&mixer.buses, &track);
+        mixer.tracks.insert(id, track);
+    }
+
     /// Tracks have much more versatility in terms of their playback controls,
     /// however they must be manually reset to start at the beginning.
+    /// Tracks are routed to the Music bus, see `MixerSystem::route_track`.
     pub fn add_track(
         mixer: &mut Mixer,
         id: AudioID,
@@ -106,19 +353,45 @@ impl MixerSystem {
         starting_point: Duration,
         repeat_infinite: bool,
     ) -> Result<()> {
-        let sink = MixerSystem::create_sink(path, true, repeat_infinite, starting_point)?;
-        mixer.tracks.insert(id, sink);
+        let sink =
+            MixerSystem::create_sink(&mixer.output, path, true, repeat_infinite, starting_point)?;
+        MixerSystem::insert_track(mixer, id, sink, BusID::MUSIC);
+        Ok(())
+    }
+
+    /// A track decoded from disk as it plays instead of being read into memory,
+    /// for long music. Playback controls are the same as for `add_track`.
+    pub fn add_streamed_track(
+        mixer: &mut Mixer,
+        id: AudioID,
+        path: &'static str,
+        starting_point: Duration,
+        repeat_infinite: bool,
+    ) -> Result<()> {
+        let sink = MixerSystem::create_streamed_sink(
+            &mixer.output,
+            path,
+            DEFAULT_BUFFERED_CHUNKS,
+            repeat_infinite,
+            starting_point,
+        )?;
+        MixerSystem::insert_track(mixer, id, sink, BusID::MUSIC);
         Ok(())
     }
 
     /// Effects can be replayed as many times as you like without reset
     /// There is a performance penality for this, however it is smaller for short effects.
+    /// Effects are routed to the SFX bus, see `MixerSystem::route_effect`.
     pub fn add_effect(mixer: &mut Mixer, id: AudioID, path: &'static str) -> Result<()> {
-        let sink = MixerSystem::create_sink(path, true, false, Duration::from_secs(0))?;
+        let mut sink =
+            MixerSystem::create_sink(&mixer.output, path, true, false, Duration::from_secs(0))?;
+        sink.bus = BusID::SFX;
         mixer.effects.insert(id, sink);
         Ok(())
     }
 
+    /// Effects play once and follow their bus while playing,
+    /// they don't start while it is paused.
     pub fn play_effect_controlled(
         mixer: &Mixer,
         id: AudioID,
@@ -129,34 +402,28 @@ impl MixerSystem {
             .effects
             .get(&id)
             .ok_or(EffectError::new("Effect not in mixer"))?;
-        let sink = Sink::try_new(&effect.stream_handle).unwrap();
-        sink.set_volume(volume);
+        let Some(sink) = MixerSystem::start_effect_sink(mixer, effect.bus, volume)? else {
+            return Ok(());
+        };
         sink.set_speed(speed);
-        let source = Decoder::new(effect.data.clone()).unwrap().repeat_infinite();
+        let source = Decoder::new(MixerSystem::effect_data(effect)?)?;
         sink.append(source);
-        sink.detach();
+        MixerSystem::add_playing_effect(mixer, sink, effect.bus, volume);
         Ok(())
     }
 
     pub fn play_effect(mixer: &Mixer, id: AudioID) -> Result<()> {
-        let effect = mixer
-            .effects
-            .get(&id)
-            .ok_or(EffectError::new("Effect not in mixer"))?;
-        let sink = Sink::try_new(&effect.stream_handle).unwrap();
-        let source = Decoder::new(effect.data.clone()).unwrap().repeat_infinite();
-        sink.append(source);
-        sink.detach();
-        Ok(())
+        MixerSystem::play_effect_controlled(mixer, id, 1.0, 1.0)
     }
 
+    /// Plays once the track's bus is not paused either.
     pub fn play_track(mixer: &Mixer, id: AudioID) -> Result<()> {
         let track = mixer
             .tracks
             .get(&id)
             .ok_or(EffectError::new("Track not in mixer"))?;
-        track.sink.as_ref().unwrap().play();
-
+        track.paused.set(false);
+        MixerSystem::apply_bus(&mixer.buses, track);
         Ok(())
     }
 
@@ -165,7 +432,9 @@ impl MixerSystem {
             .tracks
             .get(&id)
             .ok_or(EffectError::new("Track not in mixer"))?;
-        track.sink.as_ref().unwrap().pause();
+        track.paused.set(true);
+        MixerSystem::cancel_fade(track);
+        MixerSystem::apply_bus(&mixer.buses, track);
         Ok(())
     }
 
@@ -182,19 +451,11 @@ impl MixerSystem {
         let sink = track.sink.as_ref().unwrap();
         sink.pause();
         sink.clear();
-        if repeat_infinite {
-            let source = Decoder::new(track.data.clone())
-                .unwrap()
-                .repeat_infinite()
-                .skip_duration(starting_point);
-            sink.append(source);
-        } else {
-            let source = Decoder::new(track.data.clone())
-                .unwrap()
-                .skip_duration(starting_point);
-            sink.append(source);
-        }
+        MixerSystem::append_source(sink, &track.data, repeat_infinite, starting_point)?;
         sink.pause();
+        track.paused.set(true);
+        MixerSystem::cancel_fade(track);
+        MixerSystem::apply_bus(&mixer.buses, track);
         Ok(())
     }
 
@@ -207,15 +468,196 @@ impl MixerSystem {
         Ok(())
     }
 
+    /// Multiplied by the volume of the track's bus.
     pub fn set_track_volume(mixer: &Mixer, id: AudioID, volume: f32) -> Result<()> {
         let track = mixer
             .tracks
             .get(&id)
             .ok_or(EffectError::new("Track not in mixer"))?;
-        track.sink.as_ref().unwrap().set_volume(volume);
+        track.volume.set(volume);
+        MixerSystem::apply_bus(&mixer.buses, track);
+        Ok(())
+    }
+
+    pub fn route_track(mixer: &mut Mixer, id: AudioID, bus: BusID) -> Result<()> {
+        mixer.bus(bus)?;
+        let track = mixer
+            .tracks
+            .get_mut(&id)
+            .ok_or(EffectError::new("Track not in mixer"))?;
+        track.bus = bus;
+        MixerSystem::apply_bus(&mixer.buses, track);
+        Ok(())
+    }
+
+    pub fn route_effect(mixer: &mut Mixer, id: AudioID, bus: BusID) -> Result<()> {
+        mixer.bus(bus)?;
+        let effect = mixer
+            .effects
+            .get_mut(&id)
+            .ok_or(EffectError::new("Effect not in mixer"))?;
+        effect.bus = bus;
+        Ok(())
+    }
+
+    /// Adds a bus beneath `parent`, e.g. an Ambience bus under SFX.
+    pub fn add_bus(mixer: &mut Mixer, id: BusID, parent: BusID) -> Result<()> {
+        mixer.bus(parent)?;
+        if mixer.buses.contains_key(&id) {
+            return Err(EffectError::new("Bus already in mixer").into());
+        }
+        mixer.buses.insert(id, AudioBus::new(Some(parent)));
+        Ok(())
+    }
+
+    pub fn set_bus_volume(mixer: &mut Mixer, id: BusID, volume: f32) -> Result<()> {
+        MixerSystem::bus_mut(mixer, id)?.volume = volume;
+        MixerSystem::apply_buses(mixer);
+        Ok(())
+    }
+
+    pub fn set_bus_muted(mixer: &mut Mixer, id: BusID, muted: bool) -> Result<()> {
+        MixerSystem::bus_mut(mixer, id)?.muted = muted;
+        MixerSystem::apply_buses(mixer);
+        Ok(())
+    }
+
+    /// While any bus is soloed, only soloed buses and those beneath them are heard.
+    pub fn set_bus_soloed(mixer: &mut Mixer, id: BusID, soloed: bool) -> Result<()> {
+        MixerSystem::bus_mut(mixer, id)?.soloed = soloed;
+        MixerSystem::apply_buses(mixer);
+        Ok(())
+    }
+
+    /// Pauses every track and effect beneath the bus, without changing whether each track is paused,
+    /// so unpausing resumes only the tracks that were playing.
+    pub fn set_bus_paused(mixer: &mut Mixer, id: BusID, paused: bool) -> Result<()> {
+        MixerSystem::bus_mut(mixer, id)?.paused = paused;
+        MixerSystem::apply_buses(mixer);
+        Ok(())
+    }
+
+    /// Fades the track's volume to `to`, a multiplier of its own volume, over `duration`.
+    /// Fades move forward in `MixerSystem::update`.
+    pub fn fade_track(
+        mixer: &Mixer,
+        id: AudioID,
+        to: f32,
+        duration: Duration,
+        curve: FadeCurve,
+    ) -> Result<()> {
+        MixerSystem::start_fade(mixer, id, None, to, duration, curve, FadeEnd::Continue)
+    }
+
+    /// Plays the track, rising from silence. A track that is already playing,
+    /// such as one part way through fading out, rises from where it is.
+    pub fn fade_in(mixer: &Mixer, id: AudioID, duration: Duration, curve: FadeCurve) -> Result<()> {
+        let from = if mixer.is_paused(id)? {
+            Some(0.0)
+        } else {
+            None
+        };
+        MixerSystem::start_fade(mixer, id, from, 1.0, duration, curve, FadeEnd::Continue)?;
+        MixerSystem::play_track(mixer, id)
+    }
+
+    /// Fades the track to silence then pauses it, it plays at full volume when next played.
+    pub fn fade_out(
+        mixer: &Mixer,
+        id: AudioID,
+        duration: Duration,
+        curve: FadeCurve,
+    ) -> Result<()> {
+        MixerSystem::start_fade(mixer, id, None, 0.0, duration, curve, FadeEnd::Pause)
+    }
+
+    /// Fades `from` out while fading `to` in, e.g. when switching music.
+    /// `FadeCurve::EqualPower` avoids a dip in loudness half way through.
+    pub fn crossfade(
+        mixer: &Mixer,
+        from: AudioID,
+        to: AudioID,
+        duration: Duration,
+        curve: FadeCurve,
+    ) -> Result<()> {
+        // Check both first so a missing track doesn't leave one fading alone
+        mixer.is_paused(from)?;
+        mixer.is_paused(to)?;
+        MixerSystem::fade_out(mixer, from, duration, curve)?;
+        MixerSystem::fade_in(mixer, to, duration, curve)
+    }
+
+    /// Moves fades forward, call once a frame with the frame's delta time.
+    pub fn update(mixer: &Mixer, delta_time: Duration) {
+        for track in mixer.tracks.values() {
+            let Some(mut fade) = track.fade.get() else {
+                continue;
+            };
+            FadeSystem::advance(&mut fade, delta_time);
+            track.fade_gain.set(FadeSystem::gain(&fade));
+            if fade.finished() {
+                if fade.end() == FadeEnd::Pause {
+                    track.paused.set(true);
+                    track.fade_gain.set(1.0);
+                }
+                track.fade.set(None);
+            } else {
+                track.fade.set(Some(fade));
+            }
+            MixerSystem::apply_bus(&mixer.buses, track);
+        }
+    }
+
+    fn start_fade(
+        mixer: &Mixer,
+        id: AudioID,
+        from: Option<f32>,
+        to: f32,
+        duration: Duration,
+        curve: FadeCurve,
+        end: FadeEnd,
+    ) -> Result<()> {
+        let track = mixer
+            .tracks
+            .get(&id)
+            .ok_or(EffectError::new("Track not in mixer"))?;
+        let from = from.unwrap_or(track.fade_gain.get());
+        let fade = Fade::new(from, to, duration, curve, end);
+        track.fade_gain.set(FadeSystem::gain(&fade));
+        track.fade.set(Some(fade));
+        MixerSystem::apply_bus(&mixer.buses, track);
         Ok(())
     }
 
+    fn cancel_fade(track: &AudioTrack) {
+        track.fade.set(None);
+        track.fade_gain.set(1.0);
+    }
+
+    fn bus_mut(mixer: &mut Mixer, id: BusID) -> Result<&mut AudioBus> {
+        mixer
+            .buses
+            .get_mut(&id)
+            .ok_or(EffectError::new("Bus not in mixer").into())
+    }
+
+    /// Stops every track and effect, spatial ones included, call before shutting down so playback ends cleanly.
+    pub fn stop_all(mixer: &Mixer) {
+        for track in mixer.tracks.values() {
+            if let Some(sink) = track.sink.as_ref() {
+                sink.stop();
+            }
+        }
+        for effect in mixer.playing_effects.borrow_mut().drain(..) {
+            effect.sink.stop();
+        }
+        for track in mixer.spatial_tracks.borrow().iter() {
+            if let Some(track) = track.upgrade() {
+                track.sink.stop();
+            }
+        }
+    }
+
     pub fn remove_track(mixer: &mut Mixer, id: AudioID) {
         let _ = mixer.tracks.remove(&id);
     }
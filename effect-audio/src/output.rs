@@ -0,0 +1,106 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use effect_util::effect_error::EffectError;
+use rodio::{
+    dynamic_mixer::{self, DynamicMixerController},
+    OutputStream, OutputStreamHandle, Sink,
+};
+
+const NULL_CHANNELS: u16 = 2;
+const NULL_SAMPLE_RATE: u32 = 44_100;
+
+/// The device every sink plays through. Owned by the `Mixer`, so the device
+/// is opened once however many tracks and effects are loaded.
+pub struct AudioOutput {
+    device: AudioDevice,
+}
+
+enum AudioDevice {
+    Default {
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+    },
+    Null(NullDevice),
+}
+
+// Pulls samples at the rate a real device would and throws them away,
+// so playback position, track ends and detached effects behave the same
+struct NullDevice {
+    controller: Arc<DynamicMixerController<f32>>,
+    running: Arc<AtomicBool>,
+}
+
+impl Drop for NullDevice {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Release);
+    }
+}
+
+impl AudioOutput {
+    /// The system's default output device, an error when there is none.
+    pub fn new() -> Result<Self> {
+        let (_stream, handle) = OutputStream::try_default()
+            .map_err(|e| EffectError::new(&format!("Could not open audio output: {e}")))?;
+        let device = AudioDevice::Default { _stream, handle };
+        Ok(Self { device })
+    }
+
+    /// Plays nothing, for tests, servers and machines without a sound device.
+    /// Everything else works as it would with a device.
+    pub fn null() -> Self {
+        let (controller, mut mixer) = dynamic_mixer::mixer(NULL_CHANNELS, NULL_SAMPLE_RATE);
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        thread::spawn(move || {
+            let start = Instant::now();
+            let samples_per_second = NULL_SAMPLE_RATE as f64 * NULL_CHANNELS as f64;
+            let mut consumed = 0u64;
+            while thread_running.load(Ordering::Acquire) {
+                let due = (start.elapsed().as_secs_f64() * samples_per_second) as u64;
+                mixer
+                    .by_ref()
+                    .take(due.saturating_sub(consumed) as usize)
+                    .for_each(drop);
+                consumed = consumed.max(due);
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+        let device = AudioDevice::Null(NullDevice {
+            controller,
+            running,
+        });
+        Self { device }
+    }
+
+    /// The default device, or the null output when it can't be opened.
+    pub fn default_or_null() -> Self {
+        Self::new().unwrap_or_else(|_| Self::null())
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self.device, AudioDevice::Null(_))
+    }
+}
+
+pub struct AudioOutputSystem;
+
+impl AudioOutputSystem {
+    pub fn create_sink(output: &AudioOutput) -> Result<Sink> {
+        match &output.device {
+            AudioDevice::Default { handle, .. } => Ok(Sink::try_new(handle)?),
+            AudioDevice::Null(null) => {
+                let (sink, queue) = Sink::new_idle();
+                null.controller.add(queue);
+                Ok(sink)
+            }
+        }
+    }
+}
@@ -1,28 +1,37 @@
-use std::{fs::*, io::*, time::Duration};
+use std::{cell::Cell, fs::*, io::*, rc::Rc, time::Duration};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use effect_core::primitives::vector::Vector3;
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Source, SpatialSink};
+use rodio::{source::Spatial, Decoder, Sink, Source};
+
+use crate::bus::BusID;
+use crate::mixer::{Mixer, MixerSystem};
+use crate::output::AudioOutputSystem;
 
 pub struct SpatialAudioEffect {
     data: Cursor<Vec<u8>>,
+    bus: BusID,
     position: Vector3<f32>,
     left_ear: [f32; 3],
     right_ear: [f32; 3],
-    _stream: OutputStream,
-    stream_handle: OutputStreamHandle,
 }
 
 pub struct SpatialAudioTrack {
     data: Cursor<Vec<u8>>,
-    sink: Option<SpatialSink>,
-    _stream: OutputStream,
-    stream_handle: OutputStreamHandle,
+    sink: Rc<SpatialSink>,
     position: Vector3<f32>,
     left_ear: [f32; 3],
     right_ear: [f32; 3],
 }
 
+// Shared with the mixer so bus changes reach tracks the app owns
+pub(crate) struct SpatialSink {
+    pub(crate) sink: Sink,
+    pub(crate) bus: Cell<BusID>,
+    pub(crate) volume: Cell<f32>,
+    pub(crate) paused: Cell<bool>,
+}
+
 // depth should be ignored in 2D
 // taking depth into account can cause issues with how the camera is positioned
 pub struct SpatialAudioSystem;
@@ -34,6 +43,7 @@ impl SpatialAudioSystem {
         Vector3::new(entity.x - camera.x, entity.y - camera.y, 0.0)
     }
 
+    /// Effects are routed to the SFX bus, see `SpatialAudioSystem::route_effect`.
     pub fn new_effect(position: Vector3<f32>, path: &'static str) -> Result<SpatialAudioEffect> {
         let mut file: Vec<u8> = Vec::new();
         File::open(path)?.read_to_end(&mut file)?;
@@ -41,37 +51,52 @@ impl SpatialAudioSystem {
         // Having this way around means the sound positions are correct
         let right_ear = [-0.1, 0.0, 0.0];
         let left_ear = [0.1, 0.0, 0.0];
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
         Ok(SpatialAudioEffect {
             data: cursor,
+            bus: BusID::SFX,
             position,
             left_ear,
             right_ear,
-            _stream,
-            stream_handle,
         })
     }
 
-    pub fn play_effect(effect: &SpatialAudioEffect, volume: f32, speed: f32) {
-        let sink = SpatialSink::try_new(
-            &effect.stream_handle,
+    /// Plays through the mixer's output, multiplied by the effect's bus
+    /// and following it while playing. Doesn't start while the bus is paused.
+    pub fn play_effect(
+        mixer: &Mixer,
+        effect: &SpatialAudioEffect,
+        volume: f32,
+        speed: f32,
+    ) -> Result<()> {
+        let Some(sink) = MixerSystem::start_effect_sink(mixer, effect.bus, volume)? else {
+            return Ok(());
+        };
+        sink.set_speed(speed);
+        let source = Spatial::new(
+            Decoder::new(effect.data.clone()).context("Could not decode spatial effect")?,
             [effect.position.x, effect.position.y, effect.position.z],
             effect.left_ear,
             effect.right_ear,
-        )
-        .unwrap();
-        sink.set_volume(volume);
-        sink.set_speed(speed);
-        let source = Decoder::new(effect.data.clone()).unwrap();
+        );
         sink.append(source);
-        sink.detach();
+        MixerSystem::add_playing_effect(mixer, sink, effect.bus, volume);
+        Ok(())
     }
 
     pub fn set_position_effect(effect: &mut SpatialAudioEffect, position: Vector3<f32>) {
         effect.position = position;
     }
 
+    pub fn route_effect(mixer: &Mixer, effect: &mut SpatialAudioEffect, bus: BusID) -> Result<()> {
+        mixer.bus(bus)?;
+        effect.bus = bus;
+        Ok(())
+    }
+
+    /// Plays through the mixer's output and follows its buses until the track is dropped.
+    /// Tracks are routed to the SFX bus, see `SpatialAudioSystem::route_track`.
     pub fn new_track(
+        mixer: &Mixer,
         position: Vector3<f32>,
         path: &'static str,
         starting_point: Duration,
@@ -79,50 +104,60 @@ impl SpatialAudioSystem {
     ) -> Result<SpatialAudioTrack> {
         let mut file: Vec<u8> = Vec::new();
         File::open(path)?.read_to_end(&mut file)?;
-        let cursor = Cursor::new(file);
+        SpatialAudioSystem::create_track(mixer, position, file, starting_point, repeat_infinite)
+    }
+
+    fn create_track(
+        mixer: &Mixer,
+        position: Vector3<f32>,
+        data: Vec<u8>,
+        starting_point: Duration,
+        repeat_infinite: bool,
+    ) -> Result<SpatialAudioTrack> {
+        let sink = Rc::new(SpatialSink {
+            sink: AudioOutputSystem::create_sink(mixer.output())?,
+            bus: Cell::new(BusID::SFX),
+            volume: Cell::new(1.0),
+            paused: Cell::new(true),
+        });
         // Having this way around means the sound positions are correct
         let right_ear = [-0.1, 0.0, 0.0];
         let left_ear = [0.1, 0.0, 0.0];
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-        let mut track = SpatialAudioTrack {
-            data: cursor,
-            sink: None,
-            _stream,
-            stream_handle,
+        let track = SpatialAudioTrack {
+            data: Cursor::new(data),
+            sink,
             position,
             left_ear,
             right_ear,
         };
-        let sink = SpatialSink::try_new(
-            &track.stream_handle,
-            [track.position.x, track.position.y, track.position.z],
-            track.left_ear,
-            track.right_ear,
-        )
-        .unwrap();
-        if repeat_infinite {
-            let source = Decoder::new(track.data.clone())
-                .unwrap()
-                .repeat_infinite()
-                .skip_duration(starting_point);
-            sink.append(source);
-        } else {
-            let source = Decoder::new(track.data.clone())
-                .unwrap()
-                .skip_duration(starting_point);
-            sink.append(source);
-        }
-        sink.pause();
-        track.sink = Some(sink);
+        SpatialAudioSystem::append_source(&track, starting_point, repeat_infinite)?;
+        MixerSystem::add_spatial_track(mixer, Rc::downgrade(&track.sink));
+        SpatialAudioSystem::apply_bus(mixer, &track);
         Ok(track)
     }
 
-    pub fn play_track(track: &SpatialAudioTrack) {
-        track.sink.as_ref().unwrap().play();
+    /// Plays once the track's bus is not paused either.
+    pub fn play_track(mixer: &Mixer, track: &SpatialAudioTrack) {
+        track.sink.paused.set(false);
+        SpatialAudioSystem::apply_bus(mixer, track);
+    }
+
+    pub fn pause_track(mixer: &Mixer, track: &SpatialAudioTrack) {
+        track.sink.paused.set(true);
+        SpatialAudioSystem::apply_bus(mixer, track);
     }
 
-    pub fn pause_track(track: &SpatialAudioTrack) {
-        track.sink.as_ref().unwrap().pause();
+    /// Multiplied by the volume of the track's bus.
+    pub fn set_volume_track(mixer: &Mixer, track: &SpatialAudioTrack, volume: f32) {
+        track.sink.volume.set(volume);
+        SpatialAudioSystem::apply_bus(mixer, track);
+    }
+
+    pub fn route_track(mixer: &Mixer, track: &SpatialAudioTrack, bus: BusID) -> Result<()> {
+        mixer.bus(bus)?;
+        track.sink.bus.set(bus);
+        SpatialAudioSystem::apply_bus(mixer, track);
+        Ok(())
     }
 
     pub fn set_position_track(track: &mut SpatialAudioTrack, position: Vector3<f32>) {
@@ -133,20 +168,130 @@ impl SpatialAudioSystem {
         track: &mut SpatialAudioTrack,
         starting_point: Duration,
         repeat_infinite: bool,
-    ) {
-        let sink = track.sink.as_mut().unwrap();
+    ) -> Result<()> {
+        SpatialAudioSystem::append_source(track, starting_point, repeat_infinite)?;
+        track.sink.paused.set(true);
+        track.sink.sink.pause();
+        Ok(())
+    }
+
+    fn apply_bus(mixer: &Mixer, track: &SpatialAudioTrack) {
+        MixerSystem::apply_spatial_bus(mixer.buses(), &track.sink);
+    }
+
+    // Sinks don't pan by themselves, so each source is wrapped with the track's positions
+    fn append_source(
+        track: &SpatialAudioTrack,
+        starting_point: Duration,
+        repeat_infinite: bool,
+    ) -> Result<()> {
+        let sink = &track.sink.sink;
+        let emitter = [track.position.x, track.position.y, track.position.z];
         if repeat_infinite {
             let source = Decoder::new(track.data.clone())
-                .unwrap()
+                .context("Could not decode spatial track")?
                 .repeat_infinite()
                 .skip_duration(starting_point);
-            sink.append(source);
+            sink.append(Spatial::new(
+                source,
+                emitter,
+                track.left_ear,
+                track.right_ear,
+            ));
         } else {
             let source = Decoder::new(track.data.clone())
-                .unwrap()
+                .context("Could not decode spatial track")?
                 .skip_duration(starting_point);
-            sink.append(source);
+            sink.append(Spatial::new(
+                source,
+                emitter,
+                track.left_ear,
+                track.right_ear,
+            ));
         }
-        sink.pause();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::AudioOutput;
+
+    // A short 16 bit mono wav of silence
+    fn silent_wav() -> Vec<u8> {
+        let sample_rate: u32 = 8000;
+        let samples = vec![0u8; sample_rate as usize];
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + samples.len() as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&samples);
+        wav
+    }
+
+    fn track_in(mixer: &Mixer) -> SpatialAudioTrack {
+        let position = Vector3::new(0.0, 0.0, 0.0);
+        SpatialAudioSystem::create_track(mixer, position, silent_wav(), Duration::ZERO, true)
+            .unwrap()
+    }
+
+    #[test]
+    fn track_volume_is_multiplied_by_its_buses() {
+        let mut mixer = Mixer::with_output(AudioOutput::null());
+        let track = track_in(&mixer);
+        SpatialAudioSystem::set_volume_track(&mixer, &track, 0.5);
+        MixerSystem::set_bus_volume(&mut mixer, BusID::SFX, 0.5).unwrap();
+        MixerSystem::set_bus_volume(&mut mixer, BusID::MASTER, 0.5).unwrap();
+        assert_eq!(track.sink.sink.volume(), 0.125);
+
+        SpatialAudioSystem::route_track(&mixer, &track, BusID::MUSIC).unwrap();
+        assert_eq!(track.sink.sink.volume(), 0.25);
+        MixerSystem::set_bus_muted(&mut mixer, BusID::MUSIC, true).unwrap();
+        assert_eq!(track.sink.sink.volume(), 0.0);
+    }
+
+    #[test]
+    fn track_pauses_with_its_bus() {
+        let mut mixer = Mixer::with_output(AudioOutput::null());
+        let track = track_in(&mixer);
+        assert!(track.sink.sink.is_paused());
+        SpatialAudioSystem::play_track(&mixer, &track);
+        assert!(!track.sink.sink.is_paused());
+
+        MixerSystem::set_bus_paused(&mut mixer, BusID::SFX, true).unwrap();
+        assert!(track.sink.sink.is_paused());
+        MixerSystem::set_bus_paused(&mut mixer, BusID::SFX, false).unwrap();
+        assert!(!track.sink.sink.is_paused());
+
+        SpatialAudioSystem::pause_track(&mixer, &track);
+        MixerSystem::set_bus_paused(&mut mixer, BusID::MASTER, false).unwrap();
+        assert!(track.sink.sink.is_paused());
+    }
+
+    #[test]
+    fn dropped_tracks_are_left_out_of_bus_changes() {
+        let mut mixer = Mixer::with_output(AudioOutput::null());
+        let kept = track_in(&mixer);
+        drop(track_in(&mixer));
+        MixerSystem::set_bus_volume(&mut mixer, BusID::SFX, 0.5).unwrap();
+        assert_eq!(kept.sink.sink.volume(), 0.5);
+    }
+
+    #[test]
+    fn routing_to_a_missing_bus_fails() {
+        let mixer = Mixer::with_output(AudioOutput::null());
+        let track = track_in(&mixer);
+        assert!(SpatialAudioSystem::route_track(&mixer, &track, BusID("Missing")).is_err());
+        assert_eq!(track.sink.bus.get(), BusID::SFX);
     }
 }
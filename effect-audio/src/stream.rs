@@ -0,0 +1,148 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, SyncSender, TryRecvError},
+    thread,
+    time::Duration,
+};
+
+use anyhow::Result;
+use rodio::{Decoder, Source};
+
+// Samples per channel in each decoded chunk, roughly 90ms at 44.1kHz
+const CHUNK_FRAMES: usize = 4096;
+
+/// Chunks decoded ahead of playback by default, about 1.5 seconds at 44.1kHz.
+pub const DEFAULT_BUFFERED_CHUNKS: usize = 16;
+
+/// Audio decoded from disk on a background thread while it plays, so only a few
+/// chunks are ever held in memory. Use for music and ambience, short effects are
+/// cheaper to keep in memory. If decoding falls behind, silence is played
+/// until it catches up rather than stalling the audio thread.
+pub struct StreamedSource {
+    receiver: Receiver<Vec<i16>>,
+    chunk: std::vec::IntoIter<i16>,
+    channels: u16,
+    sample_rate: u32,
+    total_duration: Option<Duration>,
+}
+
+impl StreamedSource {
+    /// Opens the file and starts decoding from `starting_point`, holding at most
+    /// `buffered_chunks` chunks ahead of playback. When `repeat_infinite`
+    /// is set the file is reopened at the end and played from the start.
+    pub fn new(
+        path: impl AsRef<Path>,
+        starting_point: Duration,
+        repeat_infinite: bool,
+        buffered_chunks: usize,
+    ) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        // Decoding the header here means bad files fail when loaded, not when played
+        let decoder = Decoder::new(BufReader::new(File::open(&path)?))?;
+        let channels = decoder.channels();
+        let sample_rate = decoder.sample_rate();
+        let total_duration = if repeat_infinite {
+            None
+        } else {
+            decoder
+                .total_duration()
+                .map(|duration| duration.saturating_sub(starting_point))
+        };
+        let skip = (starting_point.as_secs_f64() * sample_rate as f64) as usize * channels as usize;
+        let (sender, receiver) = mpsc::sync_channel(buffered_chunks.max(1));
+        thread::spawn(move || {
+            StreamSystem::decode(decoder, path, skip, repeat_infinite, channels, sender)
+        });
+        let chunk = Vec::new().into_iter();
+        Ok(Self {
+            receiver,
+            chunk,
+            channels,
+            sample_rate,
+            total_duration,
+        })
+    }
+}
+
+impl Iterator for StreamedSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        loop {
+            if let Some(sample) = self.chunk.next() {
+                return Some(sample);
+            }
+            self.chunk = match self.receiver.try_recv() {
+                Ok(chunk) => chunk.into_iter(),
+                // A whole frame of silence, so the channels stay lined up
+                Err(TryRecvError::Empty) => vec![0; self.channels as usize].into_iter(),
+                Err(TryRecvError::Disconnected) => return None,
+            };
+        }
+    }
+}
+
+impl Source for StreamedSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+}
+
+struct StreamSystem;
+
+impl StreamSystem {
+    // Runs until the file ends or the source is dropped, sending blocks once the
+    // buffer is full so decoding never gets more than the buffer ahead
+    fn decode(
+        decoder: Decoder<BufReader<File>>,
+        path: PathBuf,
+        skip: usize,
+        repeat_infinite: bool,
+        channels: u16,
+        sender: SyncSender<Vec<i16>>,
+    ) {
+        let chunk_len = CHUNK_FRAMES * channels as usize;
+        let mut decoder = Some(decoder);
+        let mut skip = skip;
+        loop {
+            let mut samples = match decoder.take() {
+                Some(decoder) => decoder,
+                None => match File::open(&path).map(BufReader::new).map(Decoder::new) {
+                    Ok(Ok(decoder)) => decoder,
+                    _ => return,
+                },
+            };
+            samples.by_ref().take(skip).for_each(drop);
+            skip = 0;
+            let mut empty = true;
+            loop {
+                let chunk: Vec<i16> = samples.by_ref().take(chunk_len).collect();
+                if chunk.is_empty() {
+                    break;
+                }
+                empty = false;
+                if sender.send(chunk).is_err() {
+                    return;
+                }
+            }
+            // An empty file would otherwise be reopened forever
+            if !repeat_infinite || empty {
+                return;
+            }
+        }
+    }
+}
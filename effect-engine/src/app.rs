@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use effect_events::input::EffectEvent;
+use winit::dpi::PhysicalSize;
+
+use crate::backend::EffectBackend2D;
+use crate::control::EffectControl;
+use crate::window::WindowID;
+
+/// Implemented by games to hook into the main loop.
+/// `E` is the engine backend, implement for `EffectEngine2D<'a>` to run in a window,
+/// for `HeadlessEngine2D` to run headless, or for any `E: EffectBackend2D` to run in both.
+/// Every hook has a default so only the ones needed have to be written.
+pub trait EffectApp<E: EffectBackend2D> {
+    /// Called once the engine has been created, before the first frame.
+    fn init(&mut self, _engine: &mut E, _control: &mut EffectControl) {}
+
+    /// Called zero or more times per frame with a constant tick,
+    /// see `EffectAppBuilder::tick_rate`.
+    fn fixed_update(
+        &mut self,
+        _ctx: &mut EffectEvent,
+        _tick: Duration,
+        _control: &mut EffectControl,
+        _engine: &mut E,
+    ) {
+    }
+
+    /// Called once per frame with the real time since the last frame.
+    fn update(
+        &mut self,
+        _ctx: &mut EffectEvent,
+        _delta_time: Duration,
+        _control: &mut EffectControl,
+        _engine: &mut E,
+    ) {
+    }
+
+    /// Called once per frame after `update`, `alpha` is how far the frame is
+    /// between the last two fixed ticks.
+    fn render(&mut self, _alpha: f32, engine: &mut E) -> Result<()> {
+        engine.render()
+    }
+
+    /// Called when the user tries to close the window. Return false to keep running,
+    /// for example to ask about unsaved changes, then call `EffectControl::exit` later.
+    fn close_requested(&mut self, _control: &mut EffectControl, _engine: &mut E) -> bool {
+        true
+    }
+
+    fn resized(&mut self, _size: PhysicalSize<u32>, _engine: &mut E) {}
+
+    fn suspended(&mut self, _engine: &mut E) {}
+
+    /// Called when the app comes back after being suspended,
+    /// the first resume is reported through `init` instead.
+    fn resumed(&mut self, _engine: &mut E) {}
+
+    /// Called once an additional window requested with `EffectControl::open_window` exists.
+    /// The window's engine starts empty, load any layers and textures it draws here.
+    fn window_opened(&mut self, _id: WindowID, _engine: &mut E, _control: &mut EffectControl) {}
+
+    /// Called once per frame for each additional window, after `update`.
+    fn update_window(
+        &mut self,
+        _id: WindowID,
+        _ctx: &mut EffectEvent,
+        _delta_time: Duration,
+        _control: &mut EffectControl,
+        _engine: &mut E,
+    ) {
+    }
+
+    /// Called once per frame for each additional window, after `render`.
+    fn render_window(&mut self, _id: WindowID, _alpha: f32, engine: &mut E) -> Result<()> {
+        engine.render()
+    }
+
+    fn window_resized(&mut self, _id: WindowID, _size: PhysicalSize<u32>, _engine: &mut E) {}
+
+    /// Same as `close_requested`, but for an additional window.
+    /// Closing an additional window does not exit the app.
+    fn window_close_requested(
+        &mut self,
+        _id: WindowID,
+        _control: &mut EffectControl,
+        _engine: &mut E,
+    ) -> bool {
+        true
+    }
+
+    /// Called just before an additional window and its engine are released.
+    fn window_closed(&mut self, _id: WindowID, _engine: &mut E) {}
+
+    /// Called once as the loop exits, while the engine is still alive.
+    /// Stop audio and save state here, the engine is released straight after.
+    fn shutdown(&mut self, _engine: &mut E) {}
+}
+
+// Lets callers keep ownership of their app, mostly useful for inspecting it after a headless run
+impl<E: EffectBackend2D, A: EffectApp<E>> EffectApp<E> for &mut A {
+    fn init(&mut self, engine: &mut E, control: &mut EffectControl) {
+        (**self).init(engine, control)
+    }
+
+    fn fixed_update(
+        &mut self,
+        ctx: &mut EffectEvent,
+        tick: Duration,
+        control: &mut EffectControl,
+        engine: &mut E,
+    ) {
+        (**self).fixed_update(ctx, tick, control, engine)
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut EffectEvent,
+        delta_time: Duration,
+        control: &mut EffectControl,
+        engine: &mut E,
+    ) {
+        (**self).update(ctx, delta_time, control, engine)
+    }
+
+    fn render(&mut self, alpha: f32, engine: &mut E) -> Result<()> {
+        (**self).render(alpha, engine)
+    }
+
+    fn close_requested(&mut self, control: &mut EffectControl, engine: &mut E) -> bool {
+        (**self).close_requested(control, engine)
+    }
+
+    fn resized(&mut self, size: PhysicalSize<u32>, engine: &mut E) {
+        (**self).resized(size, engine)
+    }
+
+    fn suspended(&mut self, engine: &mut E) {
+        (**self).suspended(engine)
+    }
+
+    fn resumed(&mut self, engine: &mut E) {
+        (**self).resumed(engine)
+    }
+
+    fn window_opened(&mut self, id: WindowID, engine: &mut E, control: &mut EffectControl) {
+        (**self).window_opened(id, engine, control)
+    }
+
+    fn update_window(
+        &mut self,
+        id: WindowID,
+        ctx: &mut EffectEvent,
+        delta_time: Duration,
+        control: &mut EffectControl,
+        engine: &mut E,
+    ) {
+        (**self).update_window(id, ctx, delta_time, control, engine)
+    }
+
+    fn render_window(&mut self, id: WindowID, alpha: f32, engine: &mut E) -> Result<()> {
+        (**self).render_window(id, alpha, engine)
+    }
+
+    fn window_resized(&mut self, id: WindowID, size: PhysicalSize<u32>, engine: &mut E) {
+        (**self).window_resized(id, size, engine)
+    }
+
+    fn window_close_requested(
+        &mut self,
+        id: WindowID,
+        control: &mut EffectControl,
+        engine: &mut E,
+    ) -> bool {
+        (**self).window_close_requested(id, control, engine)
+    }
+
+    fn window_closed(&mut self, id: WindowID, engine: &mut E) {
+        (**self).window_closed(id, engine)
+    }
+
+    fn shutdown(&mut self, engine: &mut E) {
+        (**self).shutdown(engine)
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use anyhow::Result;
+use effect_util::effect_error::EffectError;
+
+use crate::settings::{EffectSettings, EffectSettingsSystem, FullScreenSetting};
+
+struct ArgSpec {
+    long: &'static str,
+    value_name: Option<&'static str>,
+    help: &'static str,
+}
+
+/// Opt-in command line options. The engine's own options are registered by `new`,
+/// games add theirs with `flag` and `option` before parsing. Anything not starting
+/// with `--`, and everything after a lone `--`, is kept as a positional argument.
+/// Apply the result to a builder with `EffectAppBuilder::args`.
+pub struct EffectArgs {
+    specs: Vec<ArgSpec>,
+    flags: HashSet<&'static str>,
+    values: HashMap<&'static str, String>,
+    positional: Vec<String>,
+    settings: EffectSettings,
+    headless: bool,
+    help_requested: bool,
+    program: String,
+}
+
+impl EffectArgs {
+    pub fn new() -> Self {
+        let specs = Vec::new();
+        let flags = HashSet::new();
+        let values = HashMap::new();
+        let positional = Vec::new();
+        let settings = EffectSettings::default();
+        let headless = false;
+        let help_requested = false;
+        let program = String::from("game");
+        Self {
+            specs,
+            flags,
+            values,
+            positional,
+            settings,
+            headless,
+            help_requested,
+            program,
+        }
+        .with_flag("windowed", "Run in a window")
+        .with_flag("borderless", "Run in borderless fullscreen")
+        .with_flag("fullscreen", "Run in exclusive fullscreen")
+        .with_option(
+            "resolution",
+            "WIDTHxHEIGHT",
+            "Window or fullscreen resolution",
+        )
+        .with_option("monitor", "INDEX", "Monitor to open the window on")
+        .with_flag("vsync", "Enable vsync")
+        .with_flag("no-vsync", "Disable vsync")
+        .with_option("target-fps", "FPS", "Cap the frame rate")
+        .with_flag("headless", "Run without a window or GPU")
+        .with_option("set", "KEY=VALUE", "Override a setting from settings.toml")
+        .with_flag("help", "Print this message")
+    }
+
+    /// Registers a `--long` flag that takes no value.
+    /// Fails if `long` is already registered, including by the engine.
+    pub fn flag(self, long: &'static str, help: &'static str) -> Result<Self> {
+        self.check_unregistered(long)?;
+        Ok(self.with_flag(long, help))
+    }
+
+    /// Registers a `--long VALUE` option, also accepted as `--long=VALUE`.
+    /// Fails if `long` is already registered, including by the engine.
+    pub fn option(
+        self,
+        long: &'static str,
+        value_name: &'static str,
+        help: &'static str,
+    ) -> Result<Self> {
+        self.check_unregistered(long)?;
+        Ok(self.with_option(long, value_name, help))
+    }
+
+    fn check_unregistered(&self, long: &str) -> Result<()> {
+        if self.specs.iter().any(|spec| spec.long == long) {
+            return Err(EffectError::new(&format!("--{long} is already registered")).into());
+        }
+        Ok(())
+    }
+
+    fn with_flag(mut self, long: &'static str, help: &'static str) -> Self {
+        self.specs.push(ArgSpec {
+            long,
+            value_name: None,
+            help,
+        });
+        self
+    }
+
+    fn with_option(
+        mut self,
+        long: &'static str,
+        value_name: &'static str,
+        help: &'static str,
+    ) -> Self {
+        self.specs.push(ArgSpec {
+            long,
+            value_name: Some(value_name),
+            help,
+        });
+        self
+    }
+
+    pub fn is_set(&self, long: &str) -> bool {
+        self.flags.contains(long)
+    }
+
+    /// Last value given for an option, if any.
+    pub fn value(&self, long: &str) -> Option<&str> {
+        self.values.get(long).map(|value| value.as_str())
+    }
+
+    /// Arguments that are not options, such as a level name or file path, in order.
+    pub fn positional(&self) -> &[String] {
+        &self.positional
+    }
+
+    pub fn help_requested(&self) -> bool {
+        self.help_requested
+    }
+
+    /// Engine options collected as settings, applied by `EffectAppBuilder::args`.
+    pub fn settings(&self) -> &EffectSettings {
+        &self.settings
+    }
+
+    pub fn headless(&self) -> bool {
+        self.headless
+    }
+
+    pub fn help(&self) -> String {
+        let mut help = format!(
+            "Usage: {} [OPTIONS] [--] [ARGS]...\n\nOptions:\n",
+            self.program
+        );
+        let usages: Vec<String> = self
+            .specs
+            .iter()
+            .map(|spec| match spec.value_name {
+                Some(value_name) => format!("--{} <{}>", spec.long, value_name),
+                None => format!("--{}", spec.long),
+            })
+            .collect();
+        let width = usages.iter().map(|usage| usage.len()).max().unwrap_or(0);
+        for (usage, spec) in usages.iter().zip(self.specs.iter()) {
+            let _ = writeln!(help, "  {usage:width$}  {}", spec.help);
+        }
+        help
+    }
+}
+
+impl Default for EffectArgs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct EffectArgsSystem;
+
+impl EffectArgsSystem {
+    /// Parses `argv`, which should not include the program name.
+    /// Unknown options and invalid values for engine options are errors.
+    pub fn parse(args: &mut EffectArgs, argv: impl IntoIterator<Item = String>) -> Result<()> {
+        let mut argv = argv.into_iter();
+        while let Some(arg) = argv.next() {
+            if arg == "--" {
+                args.positional.extend(argv);
+                break;
+            }
+            let Some(option) = arg.strip_prefix("--") else {
+                args.positional.push(arg);
+                continue;
+            };
+            let (long, inline_value) = match option.split_once('=') {
+                Some((long, value)) => (long, Some(value.to_string())),
+                None => (option, None),
+            };
+            let spec = args
+                .specs
+                .iter()
+                .find(|spec| spec.long == long)
+                .ok_or(EffectError::new(&format!("Unknown option --{long}")))?;
+            let spec_long = spec.long;
+            if spec.value_name.is_some() {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => argv
+                        .next()
+                        .ok_or(EffectError::new(&format!("--{long} needs a value")))?,
+                };
+                Self::apply_engine_option(args, spec_long, &value)?;
+                args.values.insert(spec_long, value);
+            } else {
+                if inline_value.is_some() {
+                    return Err(EffectError::new(&format!("--{long} does not take a value")).into());
+                }
+                Self::apply_engine_flag(args, spec_long);
+                args.flags.insert(spec_long);
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses the process arguments. Prints the help and exits on `--help`,
+    /// prints the error and help then exits on invalid arguments,
+    /// including any that are not valid UTF-8.
+    pub fn parse_env(args: &mut EffectArgs) {
+        let mut argv = std::env::args_os();
+        if let Some(program) = argv.next() {
+            args.program = program.to_string_lossy().into_owned();
+        }
+        let argv: Result<Vec<String>> = argv
+            .map(|arg| {
+                arg.into_string().map_err(|arg| {
+                    EffectError::new(&format!(
+                        "Argument is not valid UTF-8: {}",
+                        arg.to_string_lossy()
+                    ))
+                    .into()
+                })
+            })
+            .collect();
+        if let Err(e) = argv.and_then(|argv| Self::parse(args, argv)) {
+            eprintln!("{e}\n\n{}", args.help());
+            std::process::exit(2);
+        }
+        if args.help_requested {
+            print!("{}", args.help());
+            std::process::exit(0);
+        }
+    }
+
+    fn apply_engine_flag(args: &mut EffectArgs, long: &str) {
+        match long {
+            "windowed" => args.settings.fullscreen = Some(FullScreenSetting::Windowed),
+            "borderless" => args.settings.fullscreen = Some(FullScreenSetting::Borderless),
+            "fullscreen" => args.settings.fullscreen = Some(FullScreenSetting::Exclusive),
+            "vsync" => args.settings.vsync = Some(true),
+            "no-vsync" => args.settings.vsync = Some(false),
+            "headless" => args.headless = true,
+            "help" => args.help_requested = true,
+            _ => (),
+        }
+    }
+
+    fn apply_engine_option(args: &mut EffectArgs, long: &str, value: &str) -> Result<()> {
+        match long {
+            "resolution" => EffectSettingsSystem::apply_override(
+                &mut args.settings,
+                &format!("resolution={value}"),
+            ),
+            "monitor" => EffectSettingsSystem::apply_override(
+                &mut args.settings,
+                &format!("monitor={value}"),
+            ),
+            "target-fps" => EffectSettingsSystem::apply_override(
+                &mut args.settings,
+                &format!("target_fps={value}"),
+            ),
+            "set" => EffectSettingsSystem::apply_override(&mut args.settings, value),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &mut EffectArgs, argv: &[&str]) -> Result<()> {
+        EffectArgsSystem::parse(args, argv.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn engine_options_become_settings() {
+        let mut args = EffectArgs::new();
+        parse(
+            &mut args,
+            &[
+                "--borderless",
+                "--resolution",
+                "1920x1080",
+                "--no-vsync",
+                "--monitor=1",
+            ],
+        )
+        .unwrap();
+        let settings = args.settings();
+        assert_eq!(settings.fullscreen, Some(FullScreenSetting::Borderless));
+        assert_eq!((settings.width, settings.height), (Some(1920), Some(1080)));
+        assert_eq!(settings.vsync, Some(false));
+        assert_eq!(settings.monitor, Some(1));
+        assert!(args.is_set("no-vsync"));
+        assert_eq!(args.value("resolution"), Some("1920x1080"));
+        assert!(!args.headless());
+    }
+
+    #[test]
+    fn game_options_are_returned() {
+        let mut args = EffectArgs::new()
+            .flag("god-mode", "Take no damage")
+            .unwrap()
+            .option("level", "NAME", "Level to start on")
+            .unwrap();
+        parse(
+            &mut args,
+            &["--god-mode", "--level", "forest", "--headless"],
+        )
+        .unwrap();
+        assert!(args.is_set("god-mode"));
+        assert_eq!(args.value("level"), Some("forest"));
+        assert!(args.headless());
+    }
+
+    #[test]
+    fn positional_arguments_are_kept_in_order() {
+        let mut args = EffectArgs::new();
+        parse(
+            &mut args,
+            &["saves/one.sav", "--windowed", "-", "--", "--not-an-option"],
+        )
+        .unwrap();
+        assert_eq!(args.positional(), ["saves/one.sav", "-", "--not-an-option"]);
+        assert_eq!(
+            args.settings().fullscreen,
+            Some(FullScreenSetting::Windowed)
+        );
+    }
+
+    #[test]
+    fn invalid_arguments_are_errors() {
+        for argv in [
+            &["--unknown"][..],
+            &["--resolution"],
+            &["--resolution", "big"],
+            &["--windowed=yes"],
+            &["--set", "colour=red"],
+        ] {
+            assert!(parse(&mut EffectArgs::new(), argv).is_err(), "{argv:?}");
+        }
+    }
+
+    #[test]
+    fn registering_a_taken_name_fails() {
+        assert!(EffectArgs::new().flag("headless", "Mine").is_err());
+        assert!(EffectArgs::new().option("fps", "N", "Mine").is_ok());
+        let args = EffectArgs::new().option("level", "NAME", "Level").unwrap();
+        assert!(args.flag("level", "Again").is_err());
+    }
+
+    #[test]
+    fn help_lists_every_option() {
+        let mut args = EffectArgs::new()
+            .option("level", "NAME", "Level to start on")
+            .unwrap();
+        parse(&mut args, &["--help"]).unwrap();
+        assert!(args.help_requested());
+        let help = args.help();
+        assert!(help.contains("--level <NAME>  "));
+        assert!(help.contains("Level to start on"));
+        assert!(help.contains("--headless"));
+    }
+}
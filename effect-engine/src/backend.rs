@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use effect_core::camera::camera2d::{Camera2D, Camera2DSystem, CameraAction};
+use effect_core::id::{LayerID, TextureID};
+use effect_core::primitives::vector::Vector3;
+use effect_events::input::camera2d::{CameraTransform2D, CameraUpdateSystem2D};
+use effect_events::input::input_map::InputMap;
+use effect_events::input::EffectEvent;
+use web_render::app::effect2d::EffectEngine2D;
+use web_render::texture::texture2d::Texture2D;
+use winit::dpi::PhysicalSize;
+
+/// An entity placed on a layer, made by `EffectBackend2D::init_entity`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SceneEntity2D {
+    pub position: Vector3<f32>,
+    pub layer: LayerID,
+    pub texture: TextureID,
+}
+
+/// The parts of a 2D engine the main loops and game logic rely on, from setting up
+/// the scene to rendering each frame. Implemented by the wgpu engine and by the
+/// headless engine, so game code written against this trait runs the same with
+/// or without a GPU.
+pub trait EffectBackend2D {
+    /// The camera made by `init_camera`, `Camera2D` for the wgpu engine.
+    /// The camera systems in `effect_events` take any `CameraTransform2D`.
+    type Camera: CameraTransform2D;
+
+    /// Creates a layer holding `textures`, each `texture_size` pixels.
+    /// `pixel_art` samples the textures without smoothing.
+    fn init_layer(
+        &mut self,
+        id: LayerID,
+        textures: Vec<Texture2D>,
+        texture_size: PhysicalSize<u32>,
+        pixel_art: bool,
+    ) -> Result<()>;
+
+    fn init_entity(
+        &mut self,
+        position: Vector3<f32>,
+        layer: LayerID,
+        texture: TextureID,
+    ) -> SceneEntity2D {
+        SceneEntity2D {
+            position,
+            layer,
+            texture,
+        }
+    }
+
+    /// Replaces the entities drawn on a layer.
+    fn set_entities(&mut self, layer: LayerID, entities: &[&SceneEntity2D]) -> Result<()>;
+
+    fn init_camera(&mut self, fov: f32) -> Self::Camera;
+
+    /// See `CameraUpdateSystem2D::set_bindings`.
+    fn set_camera_bindings(&mut self, camera: &mut Self::Camera, map: &InputMap<CameraAction>);
+
+    /// Units moved per millisecond while a camera key is held.
+    fn set_camera_speed(&mut self, camera: &mut Self::Camera, speed: f32);
+
+    fn render(&mut self) -> Result<()>;
+
+    fn update(&mut self, ctx: &mut EffectEvent);
+
+    fn update_camera(&mut self, camera: &mut Self::Camera, ctx: &EffectEvent, delta_time: Duration);
+}
+
+impl<'a> EffectBackend2D for EffectEngine2D<'a> {
+    type Camera = Camera2D;
+
+    fn init_layer(
+        &mut self,
+        id: LayerID,
+        textures: Vec<Texture2D>,
+        texture_size: PhysicalSize<u32>,
+        pixel_art: bool,
+    ) -> Result<()> {
+        EffectEngine2D::init_layer(self, id, textures, texture_size, pixel_art)?;
+        Ok(())
+    }
+
+    fn set_entities(&mut self, layer: LayerID, entities: &[&SceneEntity2D]) -> Result<()> {
+        let entities: Vec<_> = entities
+            .iter()
+            .map(|entity| {
+                EffectEngine2D::init_entity(self, entity.position, entity.layer, entity.texture)
+            })
+            .collect();
+        let entities: Vec<_> = entities.iter().collect();
+        EffectEngine2D::set_entities(self, layer, &entities);
+        Ok(())
+    }
+
+    fn init_camera(&mut self, fov: f32) -> Camera2D {
+        EffectEngine2D::init_camera(self, fov)
+    }
+
+    fn set_camera_bindings(&mut self, camera: &mut Camera2D, map: &InputMap<CameraAction>) {
+        CameraUpdateSystem2D::set_bindings(camera, map);
+    }
+
+    fn set_camera_speed(&mut self, camera: &mut Camera2D, speed: f32) {
+        Camera2DSystem::set_speed(camera, speed);
+    }
+
+    fn render(&mut self) -> Result<()> {
+        EffectEngine2D::render(self)?;
+        Ok(())
+    }
+
+    fn update(&mut self, ctx: &mut EffectEvent) {
+        EffectEngine2D::update(self, ctx);
+    }
+
+    fn update_camera(&mut self, camera: &mut Camera2D, ctx: &EffectEvent, delta_time: Duration) {
+        EffectEngine2D::update_camera(self, camera, ctx, delta_time);
+    }
+}
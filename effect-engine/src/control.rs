@@ -0,0 +1,244 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use effect_core::misc::fullscreen::FullScreenMode;
+use effect_core::misc::window_info::WindowInfo;
+use winit::dpi::PhysicalSize;
+use winit::window::CursorIcon;
+
+use crate::cursor::CursorGrab;
+use crate::cursor::CursorImage;
+use crate::cursor::CursorRequest;
+
+use crate::display::DisplayMode;
+use crate::display::MonitorInfo;
+use crate::stats::FrameSample;
+use crate::stats::FrameStats;
+use crate::stats::FrameStatsSystem;
+use crate::window::WindowID;
+use crate::window::WindowRequest;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    /// The app called `EffectControl::exit`.
+    Requested,
+    /// The window was closed and `EffectApp::close_requested` allowed it.
+    CloseRequested,
+    /// A headless run reached its frame limit.
+    FrameLimit,
+    /// The platform ended the event loop without being asked to.
+    EventLoopExited,
+    /// An input replay reached the end of its recording.
+    ReplayFinished,
+}
+
+/// Handed to the app every frame to talk back to the main loop.
+/// Requests made here are applied by the loop once the current hook returns.
+pub struct EffectControl {
+    exit: Option<ExitReason>,
+    frame: u64,
+    elapsed: Duration,
+    stats: FrameStats,
+    window_requests: Vec<WindowRequest>,
+    monitors: Vec<MonitorInfo>,
+    monitors_request: bool,
+    display_request: Option<DisplayMode>,
+    ime_request: Option<bool>,
+    cursor_request: CursorRequest,
+    cursor_error: Option<String>,
+}
+
+impl EffectControl {
+    pub fn new(stats: FrameStats) -> Self {
+        let exit = None;
+        let frame = 0;
+        let elapsed = Duration::ZERO;
+        let window_requests = Vec::new();
+        let monitors = Vec::new();
+        let monitors_request = false;
+        let display_request = None;
+        let ime_request = None;
+        let cursor_request = CursorRequest::default();
+        let cursor_error = None;
+        Self {
+            exit,
+            frame,
+            elapsed,
+            stats,
+            window_requests,
+            monitors,
+            monitors_request,
+            display_request,
+            ime_request,
+            cursor_request,
+            cursor_error,
+        }
+    }
+
+    pub fn exit(&mut self) {
+        EffectControlSystem::request_exit(self, ExitReason::Requested);
+    }
+
+    pub fn exiting(&self) -> bool {
+        self.exit.is_some()
+    }
+
+    pub fn exit_reason(&self) -> Option<ExitReason> {
+        self.exit
+    }
+
+    /// Opens an additional window at the end of the frame, `EffectApp::window_opened`
+    /// is called once it exists. Opening an ID that is already open does nothing.
+    /// Each window currently gets its own GPU device, so its layers and textures
+    /// are set up in `window_opened` rather than shared with the main window.
+    pub fn open_window(&mut self, id: WindowID, window_info: WindowInfo) {
+        self.window_requests
+            .push(WindowRequest::Open(id, window_info));
+    }
+
+    /// Closes an additional window at the end of the frame, without asking the app first.
+    pub fn close_window(&mut self, id: WindowID) {
+        self.window_requests.push(WindowRequest::Close(id));
+    }
+
+    /// Monitors available as of the last refresh, empty when headless. The list is
+    /// read when the main window is created and again whenever the display mode changes.
+    pub fn monitors(&self) -> &[MonitorInfo] {
+        &self.monitors
+    }
+
+    /// Reads the monitor list again at the end of the frame,
+    /// for when monitors may have been plugged in or unplugged.
+    pub fn refresh_monitors(&mut self) {
+        self.monitors_request = true;
+    }
+
+    /// Switches the main window between windowed, borderless and exclusive fullscreen
+    /// at the end of the frame. Exclusive uses the video mode closest to `resolution`
+    /// and the refresh rate, an unknown monitor falls back to the current one.
+    pub fn set_display_mode(
+        &mut self,
+        monitor: usize,
+        fullscreen: FullScreenMode,
+        resolution: PhysicalSize<u32>,
+        refresh_rate_millihertz: Option<u32>,
+    ) {
+        self.display_request = Some(DisplayMode {
+            monitor,
+            fullscreen,
+            resolution,
+            refresh_rate_millihertz,
+        });
+    }
+
+    /// Lets the main window receive IME composition, for text fields in languages
+    /// typed through an input method. Off by default.
+    pub fn set_ime_allowed(&mut self, allowed: bool) {
+        self.ime_request = Some(allowed);
+    }
+
+    /// Confines or locks the cursor to the main window at the end of the frame.
+    /// Platforms that lack the requested mode use the other one.
+    pub fn set_cursor_grab(&mut self, grab: CursorGrab) {
+        self.cursor_request.grab = Some(grab);
+    }
+
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor_request.visible = Some(visible);
+    }
+
+    /// Switches to one of the system's cursors.
+    pub fn set_cursor_icon(&mut self, icon: CursorIcon) {
+        self.cursor_request.image = Some(CursorImage::Icon(icon));
+    }
+
+    /// Switches to a cursor loaded from an image file, such as `assets/cursor.png`.
+    /// Each image is loaded once. If it fails to load the error is logged,
+    /// the previous cursor stays and `cursor_error` reports why.
+    pub fn set_cursor_image(&mut self, path: impl Into<PathBuf>, hotspot: (u16, u16)) {
+        self.cursor_request.image = Some(CursorImage::Custom {
+            path: path.into(),
+            hotspot,
+        });
+    }
+
+    /// Why the last cursor change failed to load, cleared by the next one that succeeds.
+    pub fn cursor_error(&self) -> Option<&str> {
+        self.cursor_error.as_deref()
+    }
+
+    /// Number of frames completed so far.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Time elapsed since the first frame, synthetic when running headless.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+
+    pub fn stats_mut(&mut self) -> &mut FrameStats {
+        &mut self.stats
+    }
+}
+
+pub(crate) struct EffectControlSystem;
+
+impl EffectControlSystem {
+    /// The first reason given is kept.
+    pub fn request_exit(control: &mut EffectControl, reason: ExitReason) {
+        control.exit.get_or_insert(reason);
+    }
+
+    pub fn take_window_requests(control: &mut EffectControl) -> Vec<WindowRequest> {
+        std::mem::take(&mut control.window_requests)
+    }
+
+    pub fn take_display_request(control: &mut EffectControl) -> Option<DisplayMode> {
+        control.display_request.take()
+    }
+
+    pub fn take_ime_request(control: &mut EffectControl) -> Option<bool> {
+        control.ime_request.take()
+    }
+
+    pub fn take_cursor_request(control: &mut EffectControl) -> Option<CursorRequest> {
+        let request = std::mem::take(&mut control.cursor_request);
+        (request != CursorRequest::default()).then_some(request)
+    }
+
+    pub fn set_cursor_error(control: &mut EffectControl, error: Option<String>) {
+        control.cursor_error = error;
+    }
+
+    /// Whether the monitor list should be read again, either because the app asked
+    /// or because a display change is about to be applied.
+    pub fn take_monitors_request(control: &mut EffectControl) -> bool {
+        std::mem::take(&mut control.monitors_request) || control.display_request.is_some()
+    }
+
+    pub fn set_monitors(control: &mut EffectControl, monitors: Vec<MonitorInfo>) {
+        control.monitors = monitors;
+    }
+
+    pub fn end_frame(
+        control: &mut EffectControl,
+        frame_time: Duration,
+        update_time: Duration,
+        render_time: Duration,
+    ) {
+        let sample = FrameSample {
+            frame: control.frame,
+            frame_time,
+            update_time,
+            render_time,
+        };
+        FrameStatsSystem::record(&mut control.stats, sample);
+        control.frame += 1;
+        control.elapsed += frame_time;
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use effect_util::effect_error::EffectError;
+use winit::event_loop::ActiveEventLoop;
+use winit::window::{CursorGrabMode, CursorIcon, CustomCursor, Window};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorGrab {
+    /// The cursor moves freely.
+    None,
+    /// The cursor is kept inside the window, for edge scrolling.
+    Confined,
+    /// The cursor stays put and only `EffectEvent::mouse_delta` changes, for mouse-look.
+    Locked,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CursorImage {
+    Icon(CursorIcon),
+    /// An image file, such as `assets/cursor.png`, with the click point
+    /// given in pixels from its top left corner.
+    Custom {
+        path: PathBuf,
+        hotspot: (u16, u16),
+    },
+}
+
+/// Cursor changes requested through `EffectControl`, unset fields are left alone.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CursorRequest {
+    pub grab: Option<CursorGrab>,
+    pub visible: Option<bool>,
+    pub image: Option<CursorImage>,
+}
+
+/// Custom cursors already uploaded to the platform, keyed by image path and hotspot
+/// since the same image may be used with different click points.
+pub(crate) type CursorCache = HashMap<(PathBuf, (u16, u16)), CustomCursor>;
+
+pub struct CursorSystem;
+
+impl CursorSystem {
+    pub(crate) fn apply(
+        event_loop: &ActiveEventLoop,
+        window: &Window,
+        cache: &mut CursorCache,
+        request: CursorRequest,
+    ) -> Result<()> {
+        if let Some(grab) = request.grab {
+            Self::grab(window, grab);
+        }
+        if let Some(visible) = request.visible {
+            window.set_cursor_visible(visible);
+        }
+        match request.image {
+            Some(CursorImage::Icon(icon)) => window.set_cursor(icon),
+            Some(CursorImage::Custom { path, hotspot }) => {
+                let key = (path, hotspot);
+                let cursor = match cache.get(&key) {
+                    Some(cursor) => cursor.clone(),
+                    None => {
+                        let cursor = Self::load(event_loop, &key.0, hotspot)?;
+                        cache.insert(key, cursor.clone());
+                        cursor
+                    }
+                };
+                window.set_cursor(cursor);
+            }
+            None => (),
+        }
+        Ok(())
+    }
+
+    /// Platforms only support one of confined and locked,
+    /// so each falls back to the other rather than doing nothing.
+    pub fn grab(window: &Window, grab: CursorGrab) {
+        let (mode, fallback) = match grab {
+            CursorGrab::None => (CursorGrabMode::None, CursorGrabMode::None),
+            CursorGrab::Confined => (CursorGrabMode::Confined, CursorGrabMode::Locked),
+            CursorGrab::Locked => (CursorGrabMode::Locked, CursorGrabMode::Confined),
+        };
+        if window.set_cursor_grab(mode).is_err() {
+            let _ = window.set_cursor_grab(fallback);
+        }
+    }
+
+    fn load(
+        event_loop: &ActiveEventLoop,
+        path: &Path,
+        hotspot: (u16, u16),
+    ) -> Result<CustomCursor> {
+        let image = image::open(path)
+            .map_err(|e| {
+                EffectError::new(&format!("Could not load cursor {}: {e}", path.display()))
+            })?
+            .into_rgba8();
+        let (width, height) = image.dimensions();
+        let invalid = |e: &dyn std::fmt::Display| {
+            EffectError::new(&format!("Invalid cursor {}: {e}", path.display()))
+        };
+        let width = u16::try_from(width).map_err(|e| invalid(&e))?;
+        let height = u16::try_from(height).map_err(|e| invalid(&e))?;
+        let source = CustomCursor::from_rgba(image.into_raw(), width, height, hotspot.0, hotspot.1)
+            .map_err(|e| invalid(&e))?;
+        Ok(event_loop.create_custom_cursor(source))
+    }
+}
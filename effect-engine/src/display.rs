@@ -0,0 +1,143 @@
+use effect_core::misc::fullscreen::FullScreenMode;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event_loop::ActiveEventLoop;
+use winit::monitor::{MonitorHandle, VideoModeHandle};
+use winit::window::{Fullscreen, Window};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoModeInfo {
+    pub resolution: PhysicalSize<u32>,
+    pub refresh_rate_millihertz: u32,
+    pub bit_depth: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct MonitorInfo {
+    /// Index to pass to `EffectAppBuilder::monitor` or `EffectControl::set_display_mode`.
+    pub index: usize,
+    pub name: Option<String>,
+    pub resolution: PhysicalSize<u32>,
+    pub position: PhysicalPosition<i32>,
+    pub scale_factor: f64,
+    pub refresh_rate_millihertz: Option<u32>,
+    pub video_modes: Vec<VideoModeInfo>,
+}
+
+/// A display change requested at runtime through `EffectControl::set_display_mode`.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayMode {
+    pub monitor: usize,
+    pub fullscreen: FullScreenMode,
+    pub resolution: PhysicalSize<u32>,
+    /// `None` picks the highest refresh rate available at the resolution.
+    pub refresh_rate_millihertz: Option<u32>,
+}
+
+pub struct DisplaySystem;
+
+impl DisplaySystem {
+    pub fn monitors(event_loop: &ActiveEventLoop) -> Vec<MonitorInfo> {
+        event_loop
+            .available_monitors()
+            .enumerate()
+            .map(|(index, monitor)| MonitorInfo {
+                index,
+                name: monitor.name(),
+                resolution: monitor.size(),
+                position: monitor.position(),
+                scale_factor: monitor.scale_factor(),
+                refresh_rate_millihertz: monitor.refresh_rate_millihertz(),
+                video_modes: monitor
+                    .video_modes()
+                    .map(|mode| Self::mode_info(&mode))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Closest resolution wins, then closest refresh rate, then highest bit depth.
+    pub fn best_video_mode(
+        modes: &[VideoModeInfo],
+        resolution: PhysicalSize<u32>,
+        refresh_rate_millihertz: Option<u32>,
+    ) -> Option<VideoModeInfo> {
+        modes
+            .iter()
+            .copied()
+            .min_by_key(|mode| Self::score(mode, resolution, refresh_rate_millihertz))
+    }
+
+    /// Falls back to the window's current monitor, then the primary one,
+    /// when there is no monitor at `index`.
+    pub fn monitor(window: &Window, index: usize) -> Option<MonitorHandle> {
+        window
+            .available_monitors()
+            .nth(index)
+            .or_else(|| window.current_monitor())
+            .or_else(|| window.primary_monitor())
+    }
+
+    /// Exclusive fullscreen falls back to borderless when the monitor has no usable video mode.
+    pub fn fullscreen(
+        monitor: Option<MonitorHandle>,
+        mode: FullScreenMode,
+        resolution: PhysicalSize<u32>,
+        refresh_rate_millihertz: Option<u32>,
+    ) -> Option<Fullscreen> {
+        match mode {
+            FullScreenMode::WINDOWED => None,
+            FullScreenMode::BORDERLESS => Some(Fullscreen::Borderless(monitor)),
+            FullScreenMode::EXCLUSIVE => {
+                let video_mode = monitor.as_ref().and_then(|monitor| {
+                    monitor.video_modes().min_by_key(|mode| {
+                        Self::score(&Self::mode_info(mode), resolution, refresh_rate_millihertz)
+                    })
+                });
+                match video_mode {
+                    Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+                    None => Some(Fullscreen::Borderless(monitor)),
+                }
+            }
+        }
+    }
+
+    pub fn apply(window: &Window, display_mode: DisplayMode) {
+        let monitor = Self::monitor(window, display_mode.monitor);
+        window.set_fullscreen(Self::fullscreen(
+            monitor,
+            display_mode.fullscreen,
+            display_mode.resolution,
+            display_mode.refresh_rate_millihertz,
+        ));
+        if let FullScreenMode::WINDOWED = display_mode.fullscreen {
+            let _ = window.request_inner_size(display_mode.resolution);
+        }
+    }
+
+    fn mode_info(mode: &VideoModeHandle) -> VideoModeInfo {
+        VideoModeInfo {
+            resolution: mode.size(),
+            refresh_rate_millihertz: mode.refresh_rate_millihertz(),
+            bit_depth: mode.bit_depth(),
+        }
+    }
+
+    fn score(
+        mode: &VideoModeInfo,
+        resolution: PhysicalSize<u32>,
+        refresh_rate_millihertz: Option<u32>,
+    ) -> (u32, i64, i32) {
+        let resolution_distance = mode.resolution.width.abs_diff(resolution.width)
+            + mode.resolution.height.abs_diff(resolution.height);
+        let refresh = mode.refresh_rate_millihertz as i64;
+        let refresh_distance = match refresh_rate_millihertz {
+            Some(requested) => (refresh - requested as i64).abs(),
+            None => -refresh,
+        };
+        (
+            resolution_distance,
+            refresh_distance,
+            -(mode.bit_depth as i32),
+        )
+    }
+}
@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Result;
+use effect_core::camera::camera2d::CameraAction;
+use effect_core::id::LayerID;
+use effect_core::misc::window_info::WindowInfo;
+use effect_core::primitives::vector::Vector3;
+use effect_events::input::camera2d::CameraTransform2D;
+use effect_events::input::camera2d::CameraUpdateSystem2D;
+use effect_events::input::gamepad::GamepadBackend;
+use effect_events::input::input_event::InputEvent;
+use effect_events::input::input_map::InputMap;
+use effect_events::input::record::InputMode;
+use effect_events::input::record::InputRecorder;
+use effect_events::input::record::InputRecording;
+use effect_events::input::record::InputRecordingSystem;
+use effect_events::input::record::InputReplay;
+use effect_events::input::EffectEvent;
+use effect_events::input::EffectEventSystem;
+use effect_util::effect_error::EffectError;
+use web_render::texture::texture2d::Texture2D;
+use winit::dpi::PhysicalSize;
+use winit::keyboard::KeyCode;
+
+use crate::app::EffectApp;
+use crate::backend::EffectBackend2D;
+use crate::backend::SceneEntity2D;
+use crate::control::EffectControl;
+use crate::control::EffectControlSystem;
+use crate::control::ExitReason;
+use crate::main_loop::STATS_WINDOW;
+use crate::stats::FrameStats;
+use crate::timestep::FixedTimestep;
+use crate::timestep::FixedTimestepSystem;
+use crate::window::EffectWindow2D;
+use crate::window::WindowID;
+use crate::window::WindowRequest;
+
+/// A camera for the headless engine. It moves and builds its matrices the same way
+/// `Camera2D` does, so the camera systems work on it, it just has no GPU buffer.
+#[derive(Debug, Clone)]
+pub struct HeadlessCamera2D {
+    fov: f32,
+    position: Vector3<f32>,
+    speed: f32,
+    proj: glam::Mat4,
+    look_at: glam::Mat4,
+    key_codes: HashMap<CameraAction, KeyCode>,
+    current_actions: HashSet<CameraAction>,
+}
+
+impl HeadlessCamera2D {
+    /// Starts one unit in front of the scene, looking at it.
+    pub fn new(fov: f32, window_size: PhysicalSize<u32>) -> Self {
+        let position = Vector3::new(0.0, 0.0, 1.0);
+        let speed = 1.0;
+        let aspect = window_size.width.max(1) as f32 / window_size.height.max(1) as f32;
+        let proj = glam::Mat4::perspective_rh(fov.to_radians(), aspect, 0.1, 1000.0);
+        let look_at = glam::Mat4::look_at_rh(glam::Vec3::Z, glam::Vec3::ZERO, glam::Vec3::Y);
+        let key_codes = HashMap::new();
+        let current_actions = HashSet::new();
+        Self {
+            fov,
+            position,
+            speed,
+            proj,
+            look_at,
+            key_codes,
+            current_actions,
+        }
+    }
+
+    pub fn fov(&self) -> f32 {
+        self.fov
+    }
+}
+
+impl CameraTransform2D for HeadlessCamera2D {
+    fn position(&self) -> Vector3<f32> {
+        self.position
+    }
+
+    fn position_mut(&mut self) -> &mut Vector3<f32> {
+        &mut self.position
+    }
+
+    fn proj(&self) -> glam::Mat4 {
+        self.proj
+    }
+
+    fn set_proj(&mut self, proj: glam::Mat4) {
+        self.proj = proj;
+    }
+
+    fn look_at(&self) -> glam::Mat4 {
+        self.look_at
+    }
+
+    fn set_look_at(&mut self, look_at: glam::Mat4) {
+        self.look_at = look_at;
+    }
+}
+
+/// No-op render backend used when running without a window or GPU.
+/// Layers, entities and cameras are still tracked so game logic sees
+/// the same state it would on screen.
+pub struct HeadlessEngine2D {
+    window_info: WindowInfo,
+    frames_rendered: u64,
+    layers: HashMap<LayerID, Vec<SceneEntity2D>>,
+}
+
+impl HeadlessEngine2D {
+    pub fn new(window_info: WindowInfo) -> Self {
+        let frames_rendered = 0;
+        let layers = HashMap::new();
+        Self {
+            window_info,
+            frames_rendered,
+            layers,
+        }
+    }
+
+    pub fn window_info(&self) -> WindowInfo {
+        self.window_info
+    }
+
+    pub fn frames_rendered(&self) -> u64 {
+        self.frames_rendered
+    }
+
+    pub fn layers(&self) -> Vec<&LayerID> {
+        self.layers.keys().collect()
+    }
+
+    /// The entities last set on the layer, `None` if it was never created.
+    pub fn entities(&self, layer: LayerID) -> Option<&[SceneEntity2D]> {
+        self.layers.get(&layer).map(Vec::as_slice)
+    }
+}
+
+impl EffectBackend2D for HeadlessEngine2D {
+    type Camera = HeadlessCamera2D;
+
+    fn init_layer(
+        &mut self,
+        id: LayerID,
+        _textures: Vec<Texture2D>,
+        _texture_size: PhysicalSize<u32>,
+        _pixel_art: bool,
+    ) -> Result<()> {
+        self.layers.insert(id, Vec::new());
+        Ok(())
+    }
+
+    fn set_entities(&mut self, layer: LayerID, entities: &[&SceneEntity2D]) -> Result<()> {
+        let layer_entities = self
+            .layers
+            .get_mut(&layer)
+            .ok_or(EffectError::new("Layer not initialised"))?;
+        *layer_entities = entities.iter().map(|entity| **entity).collect();
+        Ok(())
+    }
+
+    fn init_camera(&mut self, fov: f32) -> HeadlessCamera2D {
+        HeadlessCamera2D::new(fov, self.window_info.resolution)
+    }
+
+    fn set_camera_bindings(&mut self, camera: &mut HeadlessCamera2D, map: &InputMap<CameraAction>) {
+        camera.key_codes = CameraUpdateSystem2D::key_bindings(map)
+            .into_iter()
+            .collect();
+        camera.current_actions.clear();
+    }
+
+    fn set_camera_speed(&mut self, camera: &mut HeadlessCamera2D, speed: f32) {
+        camera.speed = speed;
+    }
+
+    fn render(&mut self) -> Result<()> {
+        self.frames_rendered += 1;
+        Ok(())
+    }
+
+    fn update(&mut self, _ctx: &mut EffectEvent) {}
+
+    fn update_camera(
+        &mut self,
+        camera: &mut HeadlessCamera2D,
+        ctx: &EffectEvent,
+        delta_time: Duration,
+    ) {
+        CameraUpdateSystem2D::update_actions(&camera.key_codes, &mut camera.current_actions, ctx);
+        CameraUpdateSystem2D::move_position(
+            &mut camera.position,
+            &camera.current_actions,
+            camera.speed,
+            delta_time,
+        );
+        CameraUpdateSystem2D::update_view(camera);
+    }
+}
+
+pub struct EffectHeadlessLoop {
+    window_info: WindowInfo,
+    timestep: FixedTimestep,
+    frame_time: Duration,
+    frame_limit: Option<u64>,
+    gamepads: Option<Box<dyn GamepadBackend>>,
+    input_mode: InputMode,
+}
+
+impl EffectHeadlessLoop {
+    pub fn new(window_info: WindowInfo, timestep: FixedTimestep) -> Self {
+        let frame_time = Duration::from_secs(1) / 60;
+        let frame_limit = None;
+        let gamepads = None;
+        let input_mode = InputMode::Live;
+        Self {
+            window_info,
+            timestep,
+            frame_time,
+            frame_limit,
+            gamepads,
+            input_mode,
+        }
+    }
+
+    /// Delta handed to the app each frame, 1/60th of a second by default.
+    pub fn frame_time(mut self, frame_time: Duration) -> Self {
+        self.frame_time = frame_time;
+        self
+    }
+
+    /// Stop after the given number of frames, even if exit was never requested.
+    pub fn frame_limit(mut self, frames: u64) -> Self {
+        self.frame_limit = Some(frames);
+        self
+    }
+
+    /// Gamepad input is only read from a backend set here, real controllers are
+    /// ignored by default so runs stay reproducible.
+    pub fn gamepad_backend(mut self, backend: Box<dyn GamepadBackend>) -> Self {
+        self.gamepads = Some(backend);
+        self
+    }
+
+    /// Records gamepad input to `path`, written as each frame finishes.
+    pub fn record_input(mut self, path: impl Into<PathBuf>) -> Self {
+        self.input_mode = InputMode::Record(InputRecorder::new(path));
+        self
+    }
+
+    /// Feeds a recording to the app using the recorded frame times instead of `frame_time`,
+    /// a recording made with a window plays back the same way here.
+    /// The run ends with `ExitReason::ReplayFinished` when it runs out.
+    pub fn replay_input(mut self, recording: InputRecording) -> Self {
+        self.input_mode = InputMode::Replay(InputReplay::new(recording));
+        self
+    }
+
+    /// Drives the app from a synthetic clock until it calls `exit`
+    /// or the frame limit is reached. Nothing is presented.
+    /// Since the clock is synthetic, the number of fixed ticks per frame is deterministic.
+    pub fn run<A>(mut self, mut app: A) -> Result<ExitReason>
+    where
+        A: EffectApp<HeadlessEngine2D>,
+    {
+        let mut event = EffectEvent::new();
+        let mut control = EffectControl::new(FrameStats::new(STATS_WINDOW, self.frame_time));
+        let mut engine = HeadlessEngine2D::new(self.window_info);
+        let mut windows: HashMap<WindowID, EffectWindow2D<HeadlessEngine2D>> = HashMap::new();
+        let mut timestep = self.timestep;
+        let mut result = Ok(());
+
+        InputRecordingSystem::live_input(
+            &mut self.input_mode,
+            &mut event,
+            InputEvent::Resized(self.window_info.resolution),
+        );
+        app.init(&mut engine, &mut control);
+        while !control.exiting() {
+            if let Some(limit) = self.frame_limit {
+                if control.frame() >= limit {
+                    EffectControlSystem::request_exit(&mut control, ExitReason::FrameLimit);
+                    break;
+                }
+            }
+            if let Some(gamepads) = self.gamepads.as_mut() {
+                for gamepad_event in gamepads.poll() {
+                    for window in windows.values_mut() {
+                        EffectEventSystem::gamepad_event_update(&mut window.event, &gamepad_event);
+                    }
+                    InputRecordingSystem::live_input(
+                        &mut self.input_mode,
+                        &mut event,
+                        InputEvent::Gamepad(gamepad_event),
+                    );
+                }
+            }
+            let Some(frame_time) = InputRecordingSystem::begin_frame(
+                &mut self.input_mode,
+                &mut event,
+                self.frame_time,
+            ) else {
+                EffectControlSystem::request_exit(&mut control, ExitReason::ReplayFinished);
+                break;
+            };
+            let ticks = FixedTimestepSystem::advance(&mut timestep, frame_time);
+            let update_start = Instant::now();
+            for _ in 0..ticks {
+                app.fixed_update(&mut event, timestep.tick(), &mut control, &mut engine);
+            }
+            app.update(&mut event, frame_time, &mut control, &mut engine);
+            for (id, window) in windows.iter_mut() {
+                app.update_window(
+                    *id,
+                    &mut window.event,
+                    frame_time,
+                    &mut control,
+                    &mut window.engine,
+                );
+            }
+            let render_start = Instant::now();
+            let mut rendered = app.render(timestep.alpha(), &mut engine);
+            engine.update(&mut event);
+            for (id, window) in windows.iter_mut() {
+                let window_rendered = app.render_window(*id, timestep.alpha(), &mut window.engine);
+                window.engine.update(&mut window.event);
+                EffectEventSystem::clear_released(&mut window.event);
+                rendered = rendered.and(window_rendered);
+            }
+            let render_end = Instant::now();
+            EffectEventSystem::clear_released(&mut event);
+            let recorded = InputRecordingSystem::finish_frame(&mut self.input_mode, frame_time);
+            // Frame time stays synthetic, the CPU times are real
+            EffectControlSystem::end_frame(
+                &mut control,
+                frame_time,
+                render_start - update_start,
+                render_end - render_start,
+            );
+            if let Err(e) = rendered {
+                result = Err(e.context("Failed to render frame"));
+                break;
+            }
+            if let Err(e) = recorded {
+                result = Err(e.context("Failed to write input recording"));
+                break;
+            }
+            EffectControlSystem::take_monitors_request(&mut control);
+            // Nothing to switch, but keep the reported window info in step with the request
+            if let Some(display_mode) = EffectControlSystem::take_display_request(&mut control) {
+                engine.window_info = engine
+                    .window_info
+                    .monitor(display_mode.monitor)
+                    .fullscreen(display_mode.fullscreen)
+                    .resolution(display_mode.resolution);
+            }
+            EffectControlSystem::take_ime_request(&mut control);
+            EffectControlSystem::take_cursor_request(&mut control);
+            for request in EffectControlSystem::take_window_requests(&mut control) {
+                match request {
+                    WindowRequest::Open(id, window_info) => {
+                        if windows.contains_key(&id) {
+                            continue;
+                        }
+                        let mut window = EffectWindow2D::new(HeadlessEngine2D::new(window_info));
+                        EffectEventSystem::input_event_update(
+                            &mut window.event,
+                            &InputEvent::Resized(window_info.resolution),
+                        );
+                        app.window_opened(id, &mut window.engine, &mut control);
+                        windows.insert(id, window);
+                    }
+                    WindowRequest::Close(id) => {
+                        if let Some(mut window) = windows.remove(&id) {
+                            app.window_closed(id, &mut window.engine);
+                        }
+                    }
+                }
+            }
+        }
+        for (id, mut window) in windows.drain() {
+            app.window_closed(id, &mut window.engine);
+        }
+        app.shutdown(&mut engine);
+        // Flush even when rendering failed, that is the run worth replaying
+        let saved = InputRecordingSystem::finish(&mut self.input_mode);
+        result?;
+        saved?;
+        Ok(control.exit_reason().unwrap_or(ExitReason::Requested))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use effect_events::input::camera2d::CameraProjectionSystem2D;
+    use effect_events::input::camera_controller::{CameraController2D, CameraControllerSystem2D};
+    use effect_events::input::pixel_camera::{PixelCamera2D, PixelCameraSystem2D};
+    use winit::dpi::PhysicalPosition;
+
+    use super::*;
+
+    fn engine() -> HeadlessEngine2D {
+        let window_info = WindowInfo::default().resolution(PhysicalSize::new(640, 360));
+        HeadlessEngine2D::new(window_info)
+    }
+
+    #[test]
+    fn controller_follows_a_target_with_the_headless_camera() {
+        let mut engine = engine();
+        let mut camera = engine.init_camera(90.0);
+        let mut controller = CameraController2D::new();
+        CameraControllerSystem2D::set_follow_speed(&mut controller, 0.0);
+        CameraControllerSystem2D::follow(&mut controller, Some(glam::Vec2::new(3.0, -2.0)));
+        CameraControllerSystem2D::update(&mut controller, &mut camera, Duration::from_millis(16));
+        assert_eq!(camera.position(), Vector3::new(3.0, -2.0, 1.0));
+        let eye = camera
+            .look_at()
+            .inverse()
+            .transform_point3(glam::Vec3::ZERO);
+        assert!(eye.abs_diff_eq(glam::Vec3::new(3.0, -2.0, 1.0), 1e-5));
+    }
+
+    #[test]
+    fn pixel_projection_works_with_the_headless_camera() {
+        let window_size = PhysicalSize::new(640, 360);
+        let mut engine = engine();
+        let mut camera = engine.init_camera(90.0);
+        let mut pixel_camera = PixelCamera2D::new(PhysicalSize::new(320, 180), 16.0);
+        PixelCameraSystem2D::update(&mut pixel_camera, &mut camera, window_size);
+        // The camera looks at the origin, which lands in the middle of the window
+        let centre =
+            CameraProjectionSystem2D::world_to_screen(&camera, window_size, glam::Vec3::ZERO)
+                .unwrap();
+        assert!((centre.x - 320.0).abs() < 1e-3 && (centre.y - 180.0).abs() < 1e-3);
+        let world = CameraProjectionSystem2D::screen_to_world(
+            &camera,
+            window_size,
+            PhysicalPosition::new(640.0, 0.0),
+            0.0,
+        )
+        .unwrap();
+        // 320x180 art pixels at 16 per unit is 20x11.25 units
+        assert!(world.abs_diff_eq(glam::Vec3::new(10.0, 5.625, 0.0), 1e-3));
+    }
+}
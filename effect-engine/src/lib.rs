@@ -6,12 +6,30 @@ pub extern crate effect_util as util;
 pub extern crate effect_vulkan as vulkan;
 pub extern crate effect_wgpu as web_render;
 
+pub mod app;
+pub mod args;
+pub mod backend;
+pub mod control;
+pub mod cursor;
+pub mod display;
+pub mod headless;
 pub mod main_loop;
+pub mod pacing;
+pub mod settings;
+pub mod stats;
+pub mod timestep;
+pub mod window;
 
 use core::misc::{fullscreen::FullScreenMode, window_info::WindowInfo};
+use std::time::Duration;
 
+use args::EffectArgs;
 use effect_wgpu::app::effect2d::EffectEngine2D;
+use headless::EffectHeadlessLoop;
 use main_loop::EffectEventLoop;
+use pacing::FramePacer;
+use settings::EffectSettings;
+use timestep::FixedTimestep;
 use winit::{dpi::PhysicalSize, event_loop::EventLoop};
 
 pub enum EngineType {
@@ -27,6 +45,7 @@ pub enum GraphicsAPI {
 pub enum EffectAppVariant {
     Web2D(EffectEventLoop),
     // Web3D(EffectWeb3D),
+    Headless(EffectHeadlessLoop),
 }
 
 pub struct EffectAppBuilder {
@@ -38,6 +57,11 @@ pub struct EffectAppBuilder {
     fullscreen_mode: FullScreenMode,
     monitor: usize,
     resolution: PhysicalSize<u32>,
+    headless: bool,
+    tick_rate: u32,
+    max_catch_up: u32,
+    target_fps: Option<u32>,
+    background_fps: Option<u32>,
 }
 
 impl Default for EffectAppBuilder {
@@ -50,6 +74,11 @@ impl Default for EffectAppBuilder {
         let fullscreen_mode = FullScreenMode::WINDOWED;
         let monitor = 0;
         let resolution = PhysicalSize::new(800, 600);
+        let headless = false;
+        let tick_rate = 60;
+        let max_catch_up = 5;
+        let target_fps = None;
+        let background_fps = None;
         Self {
             engine_type,
             app_name,
@@ -59,6 +88,11 @@ impl Default for EffectAppBuilder {
             fullscreen_mode,
             monitor,
             resolution,
+            headless,
+            tick_rate,
+            max_catch_up,
+            target_fps,
+            background_fps,
         }
     }
 }
@@ -105,6 +139,91 @@ impl EffectAppBuilder {
         self
     }
 
+    /// Run without a window or GPU surface, for CI and simulations.
+    /// The user loop is driven from a synthetic clock and rendering is a no-op.
+    pub fn headless(mut self, headless: bool) -> Self {
+        self.headless = headless;
+        self
+    }
+
+    /// Number of times per second `EffectApp::fixed_update` is called,
+    /// at most one tick per nanosecond.
+    pub fn tick_rate(mut self, ticks_per_second: u32) -> Self {
+        self.tick_rate = ticks_per_second;
+        self
+    }
+
+    /// Most fixed updates run in a single frame before the simulation falls behind
+    /// instead of trying to catch up.
+    pub fn max_catch_up(mut self, ticks: u32) -> Self {
+        self.max_catch_up = ticks;
+        self
+    }
+
+    /// Cap the frame rate, independent of vsync. `None` runs as fast as possible.
+    /// When headless, this sets the rate of the synthetic clock instead.
+    pub fn target_fps(mut self, fps: Option<u32>) -> Self {
+        self.target_fps = fps;
+        self
+    }
+
+    /// Frame rate used while the window is unfocused or minimised.
+    /// `None` keeps using the target frame rate.
+    pub fn background_fps(mut self, fps: Option<u32>) -> Self {
+        self.background_fps = fps;
+        self
+    }
+
+    /// Applies the fields set in `settings`, see `EffectSettingsSystem::load_layered`.
+    pub fn settings(mut self, settings: &EffectSettings) -> Self {
+        if let Some(width) = settings.width {
+            self.resolution.width = width;
+        }
+        if let Some(height) = settings.height {
+            self.resolution.height = height;
+        }
+        if let Some(monitor) = settings.monitor {
+            self.monitor = monitor;
+        }
+        if let Some(vsync) = settings.vsync {
+            self.vsync = vsync;
+        }
+        if let Some(fullscreen) = settings.fullscreen {
+            self.fullscreen_mode = fullscreen.into();
+        }
+        if let Some(resizable) = settings.resizable {
+            self.resizable_window = resizable;
+        }
+        if settings.target_fps.is_some() {
+            self.target_fps = settings.target_fps;
+        }
+        if settings.background_fps.is_some() {
+            self.background_fps = settings.background_fps;
+        }
+        self
+    }
+
+    /// Applies options parsed from the command line, call after the options set in code
+    /// so the command line wins.
+    pub fn args(self, args: &EffectArgs) -> Self {
+        let headless = self.headless || args.headless();
+        self.settings(args.settings()).headless(headless)
+    }
+
+    /// Current configuration as settings, ready to be saved with `EffectSettingsSystem::save`.
+    pub fn to_settings(&self) -> EffectSettings {
+        EffectSettings {
+            width: Some(self.resolution.width),
+            height: Some(self.resolution.height),
+            monitor: Some(self.monitor),
+            vsync: Some(self.vsync),
+            fullscreen: Some(self.fullscreen_mode.into()),
+            resizable: Some(self.resizable_window),
+            target_fps: self.target_fps,
+            background_fps: self.background_fps,
+        }
+    }
+
     pub fn build(self) -> EffectAppVariant {
         let window_info = WindowInfo::default()
             .app_name(self.app_name)
@@ -113,12 +232,29 @@ impl EffectAppBuilder {
             .monitor(self.monitor)
             .vsync(self.vsync)
             .resolution(self.resolution);
+        let timestep = FixedTimestep::new(self.tick_rate, self.max_catch_up);
+        let pacer = FramePacer::new(self.target_fps, self.background_fps);
+        // Creating an event loop needs a display, so bail out before that
+        if self.headless {
+            return match self.engine_type {
+                EngineType::D2 => {
+                    let mut headless_loop = EffectHeadlessLoop::new(window_info, timestep);
+                    if let Some(fps) = self.target_fps {
+                        headless_loop =
+                            headless_loop.frame_time(Duration::from_secs(1) / fps.max(1));
+                    }
+                    EffectAppVariant::Headless(headless_loop)
+                }
+                _ => unimplemented!(),
+            };
+        }
         let event_loop = EventLoop::new().unwrap();
         event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
         match self.graphics_api {
             GraphicsAPI::WGPU => match self.engine_type {
                 EngineType::D2 => {
-                    let effect_loop = EffectEventLoop::new(event_loop, window_info);
+                    let effect_loop =
+                        EffectEventLoop::new(event_loop, window_info, timestep, pacer);
                     EffectAppVariant::Web2D(effect_loop)
                 }
                 _ => unimplemented!(),
@@ -138,4 +274,13 @@ impl EffectAppVariant {
             }
         }
     }
+
+    pub fn get_headless(self) -> EffectHeadlessLoop {
+        match self {
+            EffectAppVariant::Headless(val) => return val,
+            _ => {
+                panic!("App was not configured to run headless, please check your configuration.")
+            }
+        }
+    }
 }
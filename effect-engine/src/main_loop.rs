@@ -1,69 +1,117 @@
-use std::time::{Duration, Instant};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
 
+use anyhow::Result;
 use effect_core::camera::camera2d::Camera2D;
-use effect_core::misc::fullscreen::FullScreenMode;
 use effect_core::misc::window_info::WindowInfo;
+use effect_events::input::gamepad::GamepadBackend;
+use effect_events::input::gamepad::GamepadSystem;
+use effect_events::input::input_event::InputEvent;
+use effect_events::input::record::InputMode;
+use effect_events::input::record::InputRecorder;
+use effect_events::input::record::InputRecording;
+use effect_events::input::record::InputRecordingSystem;
+use effect_events::input::record::InputReplay;
 use effect_events::input::EffectEvent;
 use effect_events::input::EffectEventSystem;
+use effect_util::effect_error::EffectError;
 use web_render::app::effect2d::EffectEngine2D;
 use web_render::camera::CameraBGL;
 use web_render::engine::builders::engine2d_builder::Engine2DBuilder;
 use web_render::texture::texture2d::Texture2D;
 use web_render::texture::texture2d::Texture2DBGL;
 use winit::application::ApplicationHandler;
+use winit::event::WindowEvent;
 use winit::event_loop::ActiveEventLoop;
+use winit::event_loop::ControlFlow;
 use winit::event_loop::EventLoop;
-use winit::monitor::MonitorHandle;
-use winit::monitor::VideoModeHandle;
+use winit::window::WindowId;
 
-pub struct EffectLoop2D<'a, F>
+use crate::app::EffectApp;
+use crate::control::EffectControl;
+use crate::control::EffectControlSystem;
+use crate::control::ExitReason;
+use crate::cursor::CursorCache;
+use crate::cursor::CursorSystem;
+use crate::display::DisplaySystem;
+use crate::pacing::FramePacer;
+use crate::pacing::FramePacerSystem;
+use crate::stats::FrameStats;
+use crate::stats::FrameStatsSystem;
+use crate::timestep::FixedTimestep;
+use crate::timestep::FixedTimestepSystem;
+use crate::window::EffectWindow2D;
+use crate::window::WindowID;
+use crate::window::WindowRequest;
+
+// Number of frames kept for the rolling frame statistics
+pub(crate) const STATS_WINDOW: usize = 240;
+// Frames are judged against 60 fps when the frame rate isn't capped
+const UNCAPPED_BUDGET: Duration = Duration::from_nanos(16_666_666);
+
+pub struct EffectLoop2D<'a, A>
 where
-    F: FnMut(&mut EffectEvent, Duration, &ActiveEventLoop, &mut EffectEngine2D) -> (),
+    A: EffectApp<EffectEngine2D<'a>>,
 {
-    user_loop: F,
+    app: A,
+    control: EffectControl,
+    timestep: FixedTimestep,
+    pacer: FramePacer,
     event: EffectEvent,
     time_before: Instant,
     time_after: Instant,
     window_info: WindowInfo,
-    app: Option<EffectEngine2D<'a>>,
+    engine: Option<EffectEngine2D<'a>>,
+    main_window: Option<WindowId>,
+    windows: HashMap<WindowID, EffectWindow2D<EffectEngine2D<'a>>>,
+    window_ids: HashMap<WindowId, WindowID>,
+    gamepads: Option<Box<dyn GamepadBackend>>,
+    input_mode: InputMode,
+    cursors: CursorCache,
+    error: Option<anyhow::Error>,
 }
 
-impl<'a, F> ApplicationHandler<()> for EffectLoop2D<'a, F>
+impl<'a, A> EffectLoop2D<'a, A>
 where
-    F: FnMut(&mut EffectEvent, Duration, &ActiveEventLoop, &mut EffectEngine2D) -> (),
+    A: EffectApp<EffectEngine2D<'a>>,
 {
-    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+    // Keeps the first error, run returns it once the loop has shut down
+    fn fail(&mut self, event_loop: &ActiveEventLoop, error: anyhow::Error) {
+        self.error.get_or_insert(error);
+        event_loop.exit();
+    }
+
+    fn create_engine(
+        event_loop: &ActiveEventLoop,
+        window_info: WindowInfo,
+    ) -> Result<(WindowId, EffectEngine2D<'a>)> {
         let attributes = winit::window::Window::default_attributes()
-            .with_title(self.window_info.name)
-            .with_inner_size(self.window_info.resolution)
-            .with_resizable(self.window_info.resizable);
+            .with_title(window_info.name)
+            .with_inner_size(window_info.resolution)
+            .with_resizable(window_info.resizable);
         let window = event_loop
             .create_window(attributes)
-            .expect("Could not create window");
-
-        let mut monitors: Vec<MonitorHandle> = window.available_monitors().collect();
-        if self.window_info.monitor as usize >= monitors.len() {
-            panic!("Could not find monitor {}", self.window_info.monitor);
-        }
-        let monitor = monitors.remove(self.window_info.monitor);
-        let mut video_modes: Vec<VideoModeHandle> = monitor.video_modes().collect();
-        window.set_fullscreen(match self.window_info.fullscreen {
-            FullScreenMode::WINDOWED => None,
-            FullScreenMode::BORDERLESS => {
-                Some(winit::window::Fullscreen::Borderless(Some(monitor)))
-            }
-            FullScreenMode::EXCLUSIVE => Some(winit::window::Fullscreen::Exclusive(
-                video_modes
-                    .pop()
-                    .expect("Monitor does not support any video modes"),
-            )),
-        });
+            .map_err(|e| EffectError::new(&format!("Could not create window: {e}")))?;
+        let window_id = window.id();
 
+        let monitor = DisplaySystem::monitor(&window, window_info.monitor);
+        window.set_fullscreen(DisplaySystem::fullscreen(
+            monitor,
+            window_info.fullscreen,
+            window_info.resolution,
+            None,
+        ));
+
+        // TODO: share the device and texture storage between windows. Every window
+        // builds its own Engine2D, which creates its own device, and Engine2DBuilder
+        // can't yet be given an existing one, so textures are uploaded per window
         let bgls = vec![Texture2D::layout(), Camera2D::layout()];
-        let mut engine = pollster::block_on(
+        let engine = pollster::block_on(
             Engine2DBuilder::default()
                 .window(window)
-                .window_info(self.window_info)
+                .window_info(window_info)
                 .power_preference(wgpu::PowerPreference::HighPerformance)
                 .vertex_shader("effect-wgpu/src/shaders/shader.wgsl")
                 .fragment_shader("effect-wgpu/src/shaders/shader.wgsl")
@@ -71,8 +119,106 @@ where
                 .build(),
         );
 
-        let mut app = EffectEngine2D::new(engine);
-        self.app = Some(app);
+        Ok((window_id, EffectEngine2D::new(engine)))
+    }
+
+    fn handle_window_requests(&mut self, event_loop: &ActiveEventLoop) {
+        for request in EffectControlSystem::take_window_requests(&mut self.control) {
+            match request {
+                WindowRequest::Open(id, window_info) => {
+                    if self.windows.contains_key(&id) {
+                        continue;
+                    }
+                    let (window_id, engine) = match Self::create_engine(event_loop, window_info) {
+                        Ok(created) => created,
+                        Err(e) => {
+                            self.fail(event_loop, e);
+                            return;
+                        }
+                    };
+                    let mut window = EffectWindow2D::new(engine);
+                    EffectEventSystem::input_event_update(
+                        &mut window.event,
+                        &InputEvent::Resized(window.engine.window().inner_size()),
+                    );
+                    self.app
+                        .window_opened(id, &mut window.engine, &mut self.control);
+                    self.windows.insert(id, window);
+                    self.window_ids.insert(window_id, id);
+                }
+                WindowRequest::Close(id) => {
+                    self.close_window(id);
+                }
+            }
+        }
+    }
+
+    fn close_window(&mut self, id: WindowID) {
+        if let Some(mut window) = self.windows.remove(&id) {
+            self.app.window_closed(id, &mut window.engine);
+            self.window_ids.retain(|_, window_id| *window_id != id);
+        }
+    }
+
+    fn additional_window_event(&mut self, id: WindowID, event: WindowEvent) {
+        let Some(window) = self.windows.get_mut(&id) else {
+            return;
+        };
+        EffectEventSystem::window_event_update(&mut window.event, &event);
+        match event {
+            WindowEvent::Resized(size) => {
+                self.app.window_resized(id, size, &mut window.engine);
+            }
+            WindowEvent::CloseRequested => {
+                if self
+                    .app
+                    .window_close_requested(id, &mut self.control, &mut window.engine)
+                {
+                    self.close_window(id);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl<'a, A> ApplicationHandler<()> for EffectLoop2D<'a, A>
+where
+    A: EffectApp<EffectEngine2D<'a>>,
+{
+    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if let Some(engine) = self.engine.as_mut() {
+            self.app.resumed(engine);
+            return;
+        }
+
+        let (window_id, mut engine) = match Self::create_engine(event_loop, self.window_info) {
+            Ok(created) => created,
+            Err(e) => {
+                self.fail(event_loop, e);
+                return;
+            }
+        };
+        EffectControlSystem::set_monitors(&mut self.control, DisplaySystem::monitors(event_loop));
+        // Not every platform sends Resized for a new window
+        InputRecordingSystem::live_input(
+            &mut self.input_mode,
+            &mut self.event,
+            InputEvent::Resized(engine.window().inner_size()),
+        );
+        self.app.init(&mut engine, &mut self.control);
+        self.engine = Some(engine);
+        self.main_window = Some(window_id);
+        self.time_before = Instant::now();
+        if self.control.exiting() {
+            event_loop.exit();
+        }
+    }
+
+    fn suspended(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if let Some(engine) = self.engine.as_mut() {
+            self.app.suspended(engine);
+        }
     }
 
     fn window_event(
@@ -81,7 +227,35 @@ where
         window_id: winit::window::WindowId,
         event: winit::event::WindowEvent,
     ) {
-        EffectEventSystem::window_event_update(&mut self.event, &event);
+        if let Some(id) = self.window_ids.get(&window_id).copied() {
+            self.additional_window_event(id, event);
+            return;
+        }
+        if self.main_window != Some(window_id) {
+            return;
+        }
+        if let Some(input) = InputEvent::from_window_event(&event) {
+            InputRecordingSystem::live_input(&mut self.input_mode, &mut self.event, input);
+        }
+        FramePacerSystem::window_event_update(&mut self.pacer, &event);
+        let Some(engine) = self.engine.as_mut() else {
+            return;
+        };
+        match event {
+            WindowEvent::Resized(size) => {
+                self.app.resized(size, engine);
+            }
+            WindowEvent::CloseRequested => {
+                if self.app.close_requested(&mut self.control, engine) {
+                    EffectControlSystem::request_exit(
+                        &mut self.control,
+                        ExitReason::CloseRequested,
+                    );
+                    event_loop.exit();
+                }
+            }
+            _ => (),
+        }
     }
 
     fn user_event(&mut self, event_loop: &winit::event_loop::ActiveEventLoop, event: ()) {}
@@ -92,54 +266,258 @@ where
         device_id: winit::event::DeviceId,
         event: winit::event::DeviceEvent,
     ) {
-        EffectEventSystem::device_event_update(&mut self.event, &event);
+        // Device events are not tied to a window, so every window sees them
+        if let Some(input) = InputEvent::from_device_event(&event) {
+            InputRecordingSystem::live_input(&mut self.input_mode, &mut self.event, input);
+        }
+        for window in self.windows.values_mut() {
+            EffectEventSystem::device_event_update(&mut window.event, &event);
+        }
     }
 
     fn about_to_wait(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if event_loop.exiting() {
+            return;
+        }
+        let Some(engine) = self.engine.as_mut() else {
+            return;
+        };
+        if let Some(wake) = FramePacerSystem::wait_until(&self.pacer, Instant::now()) {
+            event_loop.set_control_flow(ControlFlow::WaitUntil(wake));
+            return;
+        }
+        FramePacerSystem::spin(&self.pacer);
+        event_loop.set_control_flow(ControlFlow::Poll);
         self.time_after = Instant::now();
-        (self.user_loop)(
+        FramePacerSystem::frame_started(&mut self.pacer, self.time_after);
+        // Gamepads are not tied to a window either
+        if let Some(gamepads) = self.gamepads.as_mut() {
+            for gamepad_event in gamepads.poll() {
+                for window in self.windows.values_mut() {
+                    EffectEventSystem::gamepad_event_update(&mut window.event, &gamepad_event);
+                }
+                InputRecordingSystem::live_input(
+                    &mut self.input_mode,
+                    &mut self.event,
+                    InputEvent::Gamepad(gamepad_event),
+                );
+            }
+        }
+        let Some(delta_time) = InputRecordingSystem::begin_frame(
+            &mut self.input_mode,
             &mut self.event,
             self.time_after - self.time_before,
-            event_loop,
-            unsafe { &mut self.app.as_mut().unwrap_unchecked() },
-        );
+        ) else {
+            EffectControlSystem::request_exit(&mut self.control, ExitReason::ReplayFinished);
+            event_loop.exit();
+            return;
+        };
+        let ticks = FixedTimestepSystem::advance(&mut self.timestep, delta_time);
+        let update_start = Instant::now();
+        for _ in 0..ticks {
+            self.app.fixed_update(
+                &mut self.event,
+                self.timestep.tick(),
+                &mut self.control,
+                engine,
+            );
+        }
+        self.app
+            .update(&mut self.event, delta_time, &mut self.control, engine);
+        for (id, window) in self.windows.iter_mut() {
+            self.app.update_window(
+                *id,
+                &mut window.event,
+                delta_time,
+                &mut self.control,
+                &mut window.engine,
+            );
+        }
+        let render_start = Instant::now();
+        let mut rendered = self.app.render(self.timestep.alpha(), engine);
+        engine.update(&mut self.event);
+        for (id, window) in self.windows.iter_mut() {
+            let window_rendered =
+                self.app
+                    .render_window(*id, self.timestep.alpha(), &mut window.engine);
+            window.engine.update(&mut window.event);
+            EffectEventSystem::clear_released(&mut window.event);
+            rendered = rendered.and(window_rendered);
+        }
+        let render_end = Instant::now();
         self.time_before = self.time_after;
         EffectEventSystem::clear_released(&mut self.event);
+        let recorded = InputRecordingSystem::finish_frame(&mut self.input_mode, delta_time);
+        // The pacer slows down in the background, so judge each frame by the rate it ran at
+        let budget = self.pacer.budget().unwrap_or(UNCAPPED_BUDGET);
+        FrameStatsSystem::set_budget(self.control.stats_mut(), budget);
+        EffectControlSystem::end_frame(
+            &mut self.control,
+            delta_time,
+            render_start - update_start,
+            render_end - render_start,
+        );
+        if let Err(e) = rendered {
+            self.fail(event_loop, e.context("Failed to render frame"));
+            return;
+        }
+        if let Err(e) = recorded {
+            self.fail(event_loop, e.context("Failed to write input recording"));
+            return;
+        }
+        let mut cursor_result = None;
+        if let Some(engine) = self.engine.as_ref() {
+            if EffectControlSystem::take_monitors_request(&mut self.control) {
+                EffectControlSystem::set_monitors(
+                    &mut self.control,
+                    DisplaySystem::monitors(event_loop),
+                );
+            }
+            if let Some(display_mode) = EffectControlSystem::take_display_request(&mut self.control)
+            {
+                DisplaySystem::apply(engine.window(), display_mode);
+            }
+            if let Some(allowed) = EffectControlSystem::take_ime_request(&mut self.control) {
+                engine.window().set_ime_allowed(allowed);
+            }
+            if let Some(request) = EffectControlSystem::take_cursor_request(&mut self.control) {
+                let changes_image = request.image.is_some();
+                let result =
+                    CursorSystem::apply(event_loop, engine.window(), &mut self.cursors, request);
+                cursor_result = changes_image.then_some(result);
+            }
+        }
+        // A missing cursor is cosmetic, keep the old one and let the app know
+        if let Some(result) = cursor_result {
+            let error = result.err().map(|e| format!("{e:#}"));
+            if let Some(error) = &error {
+                eprintln!("Failed to set cursor: {error}");
+            }
+            EffectControlSystem::set_cursor_error(&mut self.control, error);
+        }
+        self.handle_window_requests(event_loop);
+        if self.control.exiting() {
+            event_loop.exit();
+        }
+    }
+
+    fn exiting(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        EffectControlSystem::request_exit(&mut self.control, ExitReason::EventLoopExited);
+        let ids: Vec<WindowID> = self.windows.keys().copied().collect();
+        for id in ids {
+            self.close_window(id);
+        }
+        // Let the app tear down first, then release the GPU before the event loop goes away
+        if let Some(mut engine) = self.engine.take() {
+            self.app.shutdown(&mut engine);
+        }
+        if let Err(e) = InputRecordingSystem::finish(&mut self.input_mode) {
+            self.error
+                .get_or_insert(e.context("Failed to save input recording"));
+        }
     }
 }
 
 pub struct EffectEventLoop {
     event_loop: EventLoop<()>,
     window_info: WindowInfo,
+    timestep: FixedTimestep,
+    pacer: FramePacer,
+    gamepads: Option<Box<dyn GamepadBackend>>,
+    input_mode: InputMode,
 }
 
 impl EffectEventLoop {
-    pub fn new(event_loop: EventLoop<()>, window_info: WindowInfo) -> Self {
+    pub fn new(
+        event_loop: EventLoop<()>,
+        window_info: WindowInfo,
+        timestep: FixedTimestep,
+        pacer: FramePacer,
+    ) -> Self {
+        let gamepads = GamepadSystem::default_backend();
+        let input_mode = InputMode::Live;
         Self {
             event_loop,
             window_info,
+            timestep,
+            pacer,
+            gamepads,
+            input_mode,
         }
     }
 
-    pub fn run<F>(self, user_loop: F)
+    /// Records the main window's input and frame times to `path`, written as each
+    /// frame finishes so a crash keeps everything up to the frame it happened in.
+    /// Additional windows are not recorded.
+    pub fn record_input(mut self, path: impl Into<PathBuf>) -> Self {
+        self.input_mode = InputMode::Record(InputRecorder::new(path));
+        self
+    }
+
+    /// Feeds a recording to the main window instead of live input, using the recorded
+    /// frame times. The loop exits with `ExitReason::ReplayFinished` when it runs out.
+    pub fn replay_input(mut self, recording: InputRecording) -> Self {
+        self.input_mode = InputMode::Replay(InputReplay::new(recording));
+        self
+    }
+
+    /// Replaces the default gamepad backend, e.g. with a `MockGamepadBackend`.
+    pub fn gamepad_backend(mut self, backend: Box<dyn GamepadBackend>) -> Self {
+        self.gamepads = Some(backend);
+        self
+    }
+
+    /// Runs until the app exits, returning why it stopped.
+    /// Errors from the event loop, window creation or rendering are returned instead.
+    pub fn run<'a, A>(self, app: A) -> Result<ExitReason>
     where
-        F: FnMut(&mut EffectEvent, Duration, &ActiveEventLoop, &mut EffectEngine2D) -> (),
+        A: EffectApp<EffectEngine2D<'a>>,
     {
+        let budget = self.pacer.budget().unwrap_or(UNCAPPED_BUDGET);
+        let control = EffectControl::new(FrameStats::new(STATS_WINDOW, budget));
         let event = EffectEvent::new();
         let time_before = Instant::now();
         let time_after = Instant::now();
         let window_info = self.window_info;
-        let app = None;
+        let timestep = self.timestep;
+        let pacer = self.pacer;
+        let engine = None;
+        let main_window = None;
+        let windows = HashMap::new();
+        let window_ids = HashMap::new();
+        let gamepads = self.gamepads;
+        let input_mode = self.input_mode;
+        let cursors = HashMap::new();
+        let error = None;
 
         let mut effect_loop = EffectLoop2D {
-            user_loop,
+            app,
+            control,
+            timestep,
+            pacer,
             event,
             time_before,
             time_after,
             window_info,
-            app,
+            engine,
+            main_window,
+            windows,
+            window_ids,
+            gamepads,
+            input_mode,
+            cursors,
+            error,
         };
 
-        let _ = self.event_loop.run_app(&mut effect_loop);
+        self.event_loop
+            .run_app(&mut effect_loop)
+            .map_err(|e| EffectError::new(&format!("Event loop failed: {e}")))?;
+        if let Some(error) = effect_loop.error {
+            return Err(error);
+        }
+        Ok(effect_loop
+            .control
+            .exit_reason()
+            .unwrap_or(ExitReason::EventLoopExited))
     }
 }
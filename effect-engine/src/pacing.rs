@@ -0,0 +1,161 @@
+use std::time::{Duration, Instant};
+
+use winit::event::WindowEvent;
+
+// Sleeping is only accurate to a millisecond or two on most platforms,
+// so the last stretch before a frame is spun instead.
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+/// Caps the frame rate independently of vsync.
+/// When the window is unfocused, covered or minimised the background rate is used instead.
+#[derive(Debug, Clone, Copy)]
+pub struct FramePacer {
+    target: Option<Duration>,
+    background: Option<Duration>,
+    focused: bool,
+    occluded: bool,
+    zero_size: bool,
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    pub fn new(target_fps: Option<u32>, background_fps: Option<u32>) -> Self {
+        let target = target_fps.map(|fps| Duration::from_secs(1) / fps.max(1));
+        let background = background_fps.map(|fps| Duration::from_secs(1) / fps.max(1));
+        let focused = true;
+        let occluded = false;
+        let zero_size = false;
+        let next_frame = Instant::now();
+        Self {
+            target,
+            background,
+            focused,
+            occluded,
+            zero_size,
+            next_frame,
+        }
+    }
+
+    /// Time allowed for the current frame, `None` when uncapped.
+    pub fn budget(&self) -> Option<Duration> {
+        if self.in_background() {
+            self.background.or(self.target)
+        } else {
+            self.target
+        }
+    }
+
+    pub fn in_background(&self) -> bool {
+        !self.focused || self.occluded || self.zero_size
+    }
+}
+
+pub struct FramePacerSystem;
+
+impl FramePacerSystem {
+    pub fn window_event_update(pacer: &mut FramePacer, event: &WindowEvent) {
+        match event {
+            WindowEvent::Focused(focused) => {
+                pacer.focused = *focused;
+            }
+            WindowEvent::Occluded(occluded) => {
+                pacer.occluded = *occluded;
+            }
+            // Some platforms report minimising as a resize to nothing rather than occlusion,
+            // tracked apart so a resize while covered doesn't undo the occlusion
+            WindowEvent::Resized(size) => {
+                pacer.zero_size = size.width == 0 || size.height == 0;
+            }
+            _ => (),
+        };
+    }
+
+    /// When the next frame is far enough away to sleep, returns when the loop should wake up.
+    /// `None` means the frame is due, or close enough that `spin` should be used.
+    pub fn wait_until(pacer: &FramePacer, now: Instant) -> Option<Instant> {
+        pacer.budget()?;
+        if now + SPIN_THRESHOLD < pacer.next_frame {
+            Some(pacer.next_frame - SPIN_THRESHOLD)
+        } else {
+            None
+        }
+    }
+
+    /// Busy waits out whatever is left before the next frame.
+    pub fn spin(pacer: &FramePacer) {
+        if pacer.budget().is_none() {
+            return;
+        }
+        while Instant::now() < pacer.next_frame {
+            std::hint::spin_loop();
+        }
+    }
+
+    /// Schedules the next frame, call when a frame begins.
+    pub fn frame_started(pacer: &mut FramePacer, now: Instant) {
+        let Some(budget) = pacer.budget() else {
+            pacer.next_frame = now;
+            return;
+        };
+        pacer.next_frame += budget;
+        // Don't try to make up for frames that were missed entirely
+        if pacer.next_frame < now {
+            pacer.next_frame = now + budget;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::dpi::PhysicalSize;
+
+    use super::*;
+
+    fn pacer() -> FramePacer {
+        FramePacer::new(Some(100), Some(10))
+    }
+
+    #[test]
+    fn focus_loss_uses_the_background_rate() {
+        let mut pacer = pacer();
+        assert_eq!(pacer.budget(), Some(Duration::from_millis(10)));
+        FramePacerSystem::window_event_update(&mut pacer, &WindowEvent::Focused(false));
+        assert_eq!(pacer.budget(), Some(Duration::from_millis(100)));
+        FramePacerSystem::window_event_update(&mut pacer, &WindowEvent::Focused(true));
+        assert!(!pacer.in_background());
+    }
+
+    #[test]
+    fn resizing_while_occluded_stays_in_the_background() {
+        let mut pacer = pacer();
+        FramePacerSystem::window_event_update(&mut pacer, &WindowEvent::Occluded(true));
+        let resized = WindowEvent::Resized(PhysicalSize::new(1280, 720));
+        FramePacerSystem::window_event_update(&mut pacer, &resized);
+        assert!(pacer.in_background());
+        FramePacerSystem::window_event_update(&mut pacer, &WindowEvent::Occluded(false));
+        assert!(!pacer.in_background());
+    }
+
+    #[test]
+    fn revealing_a_minimised_window_stays_in_the_background() {
+        let mut pacer = pacer();
+        let minimised = WindowEvent::Resized(PhysicalSize::new(0, 0));
+        FramePacerSystem::window_event_update(&mut pacer, &minimised);
+        FramePacerSystem::window_event_update(&mut pacer, &WindowEvent::Occluded(false));
+        assert!(pacer.in_background());
+        let restored = WindowEvent::Resized(PhysicalSize::new(1280, 720));
+        FramePacerSystem::window_event_update(&mut pacer, &restored);
+        assert!(!pacer.in_background());
+    }
+
+    #[test]
+    fn missed_frames_are_not_made_up() {
+        let mut pacer = pacer();
+        let start = pacer.next_frame;
+        FramePacerSystem::frame_started(&mut pacer, start);
+        assert_eq!(pacer.next_frame, start + Duration::from_millis(10));
+        let late = start + Duration::from_millis(50);
+        FramePacerSystem::frame_started(&mut pacer, late);
+        assert_eq!(pacer.next_frame, late + Duration::from_millis(10));
+    }
+}
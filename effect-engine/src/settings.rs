@@ -0,0 +1,290 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use effect_core::misc::fullscreen::FullScreenMode;
+use effect_util::effect_error::EffectError;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FullScreenSetting {
+    Windowed,
+    Borderless,
+    Exclusive,
+}
+
+impl From<FullScreenSetting> for FullScreenMode {
+    fn from(setting: FullScreenSetting) -> Self {
+        match setting {
+            FullScreenSetting::Windowed => FullScreenMode::WINDOWED,
+            FullScreenSetting::Borderless => FullScreenMode::BORDERLESS,
+            FullScreenSetting::Exclusive => FullScreenMode::EXCLUSIVE,
+        }
+    }
+}
+
+impl From<FullScreenMode> for FullScreenSetting {
+    fn from(mode: FullScreenMode) -> Self {
+        match mode {
+            FullScreenMode::WINDOWED => FullScreenSetting::Windowed,
+            FullScreenMode::BORDERLESS => FullScreenSetting::Borderless,
+            FullScreenMode::EXCLUSIVE => FullScreenSetting::Exclusive,
+        }
+    }
+}
+
+/// Player adjustable options for `EffectAppBuilder`, stored as TOML.
+/// Every field is optional, unset fields keep whatever the builder already had,
+/// which lets a user file override only some of the shipped defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EffectSettings {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub monitor: Option<usize>,
+    pub vsync: Option<bool>,
+    pub fullscreen: Option<FullScreenSetting>,
+    pub resizable: Option<bool>,
+    pub target_fps: Option<u32>,
+    pub background_fps: Option<u32>,
+}
+
+pub struct EffectSettingsSystem;
+
+impl EffectSettingsSystem {
+    pub fn load(path: impl AsRef<Path>) -> Result<EffectSettings> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(|e| {
+            EffectError::new(&format!("Invalid settings file {}: {e}", path.display())).into()
+        })
+    }
+
+    /// Creates the parent directory if needed.
+    pub fn save(settings: &EffectSettings, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, toml::to_string_pretty(settings)?)?;
+        Ok(())
+    }
+
+    /// `settings.toml` in the platform's per user config directory, under the app's name.
+    pub fn user_path(app_name: &str) -> Option<PathBuf> {
+        let config_dir = if cfg!(windows) {
+            std::env::var_os("APPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join("Library/Application Support"))
+        } else {
+            std::env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| {
+                    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+                })
+        };
+        config_dir.map(|dir| dir.join(app_name).join("settings.toml"))
+    }
+
+    /// Loads `path`, then the user's file for `app_name`, then applies `--set key=value`
+    /// overrides from `args`, each layer overriding the last. Missing files are skipped.
+    pub fn load_layered(
+        path: impl AsRef<Path>,
+        app_name: &str,
+        args: impl IntoIterator<Item = String>,
+    ) -> Result<EffectSettings> {
+        let mut settings = EffectSettings::default();
+        let mut paths = vec![path.as_ref().to_path_buf()];
+        paths.extend(Self::user_path(app_name));
+        for path in paths {
+            if path.is_file() {
+                Self::merge(&mut settings, &Self::load(&path)?);
+            }
+        }
+        Self::apply_args(&mut settings, args)?;
+        Ok(settings)
+    }
+
+    /// Fields set in `overlay` replace those in `settings`.
+    pub fn merge(settings: &mut EffectSettings, overlay: &EffectSettings) {
+        settings.width = overlay.width.or(settings.width);
+        settings.height = overlay.height.or(settings.height);
+        settings.monitor = overlay.monitor.or(settings.monitor);
+        settings.vsync = overlay.vsync.or(settings.vsync);
+        settings.fullscreen = overlay.fullscreen.or(settings.fullscreen);
+        settings.resizable = overlay.resizable.or(settings.resizable);
+        settings.target_fps = overlay.target_fps.or(settings.target_fps);
+        settings.background_fps = overlay.background_fps.or(settings.background_fps);
+    }
+
+    /// Applies every `--set key=value` pair in `args`, other arguments are ignored.
+    pub fn apply_args(
+        settings: &mut EffectSettings,
+        args: impl IntoIterator<Item = String>,
+    ) -> Result<()> {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if let Some(pair) = arg.strip_prefix("--set=") {
+                Self::apply_override(settings, pair)?;
+            } else if arg == "--set" {
+                let pair = args
+                    .next()
+                    .ok_or(EffectError::new("--set needs a key=value pair"))?;
+                Self::apply_override(settings, &pair)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies a single `key=value` override, such as `resolution=1920x1080` or `vsync=false`.
+    pub fn apply_override(settings: &mut EffectSettings, pair: &str) -> Result<()> {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or(EffectError::new(&format!("Expected key=value, got {pair}")))?;
+        let (key, value) = (key.trim(), value.trim());
+        let invalid = || EffectError::new(&format!("Invalid value for {key}: {value}"));
+        match key {
+            "resolution" => {
+                let (width, height) = Self::parse_resolution(value).ok_or_else(invalid)?;
+                settings.width = Some(width);
+                settings.height = Some(height);
+            }
+            "width" => settings.width = Some(value.parse().map_err(|_| invalid())?),
+            "height" => settings.height = Some(value.parse().map_err(|_| invalid())?),
+            "monitor" => settings.monitor = Some(value.parse().map_err(|_| invalid())?),
+            "vsync" => settings.vsync = Some(value.parse().map_err(|_| invalid())?),
+            "resizable" => settings.resizable = Some(value.parse().map_err(|_| invalid())?),
+            "fullscreen" => {
+                settings.fullscreen = Some(match value {
+                    "windowed" => FullScreenSetting::Windowed,
+                    "borderless" => FullScreenSetting::Borderless,
+                    "exclusive" => FullScreenSetting::Exclusive,
+                    _ => return Err(invalid().into()),
+                });
+            }
+            "target_fps" => settings.target_fps = Some(value.parse().map_err(|_| invalid())?),
+            "background_fps" => {
+                settings.background_fps = Some(value.parse().map_err(|_| invalid())?)
+            }
+            _ => return Err(EffectError::new(&format!("Unknown setting {key}")).into()),
+        }
+        Ok(())
+    }
+
+    /// Parses `WIDTHxHEIGHT`, e.g. `1920x1080`.
+    pub fn parse_resolution(value: &str) -> Option<(u32, u32)> {
+        let (width, height) = value.split_once(['x', 'X'])?;
+        Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EffectAppBuilder;
+
+    // Unique per test and process so tests can run in parallel
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("effect-settings-{}-{name}", std::process::id()))
+            .join("settings.toml")
+    }
+
+    fn write(path: &Path, text: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+
+    fn remove(path: &Path) {
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let path = temp_path("round-trip");
+        let settings = EffectSettings {
+            width: Some(1920),
+            height: Some(1080),
+            monitor: Some(1),
+            vsync: Some(false),
+            fullscreen: Some(FullScreenSetting::Borderless),
+            resizable: Some(true),
+            target_fps: Some(144),
+            background_fps: None,
+        };
+        // Also creates the missing directory
+        EffectSettingsSystem::save(&settings, &path).unwrap();
+        let loaded = EffectSettingsSystem::load(&path).unwrap();
+        remove(&path);
+        assert_eq!(loaded, settings);
+    }
+
+    #[test]
+    fn missing_files_fail_to_load_but_are_skipped_when_layered() {
+        let path = temp_path("missing");
+        assert!(EffectSettingsSystem::load(&path).is_err());
+        let app_name = format!("effect-settings-test-{}", std::process::id());
+        let settings = EffectSettingsSystem::load_layered(&path, &app_name, Vec::new()).unwrap();
+        assert_eq!(settings, EffectSettings::default());
+    }
+
+    #[test]
+    fn partial_files_keep_the_builder_defaults() {
+        let path = temp_path("partial");
+        write(&path, "vsync = false\nwidth = 1280\n");
+        let settings = EffectSettingsSystem::load(&path).unwrap();
+        remove(&path);
+        assert_eq!(settings.vsync, Some(false));
+        assert_eq!(settings.width, Some(1280));
+        assert_eq!(settings.height, None);
+
+        let defaults = EffectAppBuilder::default().to_settings();
+        let merged = EffectAppBuilder::default()
+            .settings(&settings)
+            .to_settings();
+        assert_eq!(merged.vsync, Some(false));
+        assert_eq!(merged.width, Some(1280));
+        assert_eq!(merged.height, defaults.height);
+        assert_eq!(merged.fullscreen, defaults.fullscreen);
+        assert_eq!(merged.monitor, defaults.monitor);
+    }
+
+    #[test]
+    fn invalid_files_report_the_path() {
+        let path = temp_path("invalid");
+        write(&path, "vsync = \"sometimes\"\n");
+        let error = EffectSettingsSystem::load(&path).unwrap_err();
+        remove(&path);
+        assert!(error.to_string().contains(&path.display().to_string()));
+    }
+
+    #[test]
+    fn later_layers_and_overrides_win() {
+        let path = temp_path("layered");
+        write(&path, "width = 1280\nheight = 720\nvsync = true\n");
+        let app_name = format!("effect-settings-test-{}", std::process::id());
+        let args = ["--set", "resolution=800x600", "--set=fullscreen=exclusive"];
+        let settings = EffectSettingsSystem::load_layered(
+            &path,
+            &app_name,
+            args.into_iter().map(String::from),
+        )
+        .unwrap();
+        remove(&path);
+        assert_eq!(settings.width, Some(800));
+        assert_eq!(settings.height, Some(600));
+        assert_eq!(settings.vsync, Some(true));
+        assert_eq!(settings.fullscreen, Some(FullScreenSetting::Exclusive));
+    }
+
+    #[test]
+    fn unknown_overrides_are_errors() {
+        let mut settings = EffectSettings::default();
+        assert!(EffectSettingsSystem::apply_override(&mut settings, "colour=red").is_err());
+        assert!(EffectSettingsSystem::apply_override(&mut settings, "vsync").is_err());
+        assert!(EffectSettingsSystem::apply_override(&mut settings, "width=wide").is_err());
+        assert_eq!(settings, EffectSettings::default());
+    }
+}
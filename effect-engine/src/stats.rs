@@ -0,0 +1,217 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Result;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FrameSample {
+    pub frame: u64,
+    pub frame_time: Duration,
+    /// CPU time spent in `fixed_update` and `update`.
+    pub update_time: Duration,
+    /// CPU time spent in `render` and submitting the frame.
+    pub render_time: Duration,
+}
+
+/// Rolling frame timings over the last `capacity` frames.
+/// Available to game code through `EffectControl::stats`.
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    samples: VecDeque<FrameSample>,
+    capacity: usize,
+    budget: Duration,
+    frames: u64,
+    frames_over_budget: u64,
+}
+
+impl FrameStats {
+    pub fn new(capacity: usize, budget: Duration) -> Self {
+        let capacity = capacity.max(1);
+        let samples = VecDeque::with_capacity(capacity);
+        let frames = 0;
+        let frames_over_budget = 0;
+        Self {
+            samples,
+            capacity,
+            budget,
+            frames,
+            frames_over_budget,
+        }
+    }
+
+    pub fn samples(&self) -> impl Iterator<Item = &FrameSample> {
+        self.samples.iter()
+    }
+
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Frames recorded since the stats were created or reset, not just the rolling window.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Frames that took longer than the budget since the stats were created or reset.
+    /// Each frame is judged against the budget at the time it was recorded.
+    pub fn frames_over_budget(&self) -> u64 {
+        self.frames_over_budget
+    }
+
+    pub fn average_frame_time(&self) -> Duration {
+        Self::average(self.samples.iter().map(|sample| sample.frame_time))
+    }
+
+    pub fn min_frame_time(&self) -> Duration {
+        self.samples
+            .iter()
+            .map(|sample| sample.frame_time)
+            .min()
+            .unwrap_or_default()
+    }
+
+    pub fn max_frame_time(&self) -> Duration {
+        self.samples
+            .iter()
+            .map(|sample| sample.frame_time)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Frame time that `percentile` percent of frames were at or under, e.g. 99.0 for the 1% lows.
+    pub fn percentile_frame_time(&self, percentile: f32) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::ZERO;
+        }
+        let mut times: Vec<Duration> = self.samples.iter().map(|s| s.frame_time).collect();
+        times.sort_unstable();
+        let rank = (percentile.clamp(0.0, 100.0) / 100.0 * (times.len() - 1) as f32).round();
+        times[rank as usize]
+    }
+
+    pub fn fps(&self) -> f32 {
+        let average = self.average_frame_time();
+        if average.is_zero() {
+            return 0.0;
+        }
+        1.0 / average.as_secs_f32()
+    }
+
+    pub fn average_update_time(&self) -> Duration {
+        Self::average(self.samples.iter().map(|sample| sample.update_time))
+    }
+
+    pub fn average_render_time(&self) -> Duration {
+        Self::average(self.samples.iter().map(|sample| sample.render_time))
+    }
+
+    fn average(times: impl ExactSizeIterator<Item = Duration>) -> Duration {
+        let count = times.len() as u32;
+        if count == 0 {
+            return Duration::ZERO;
+        }
+        times.sum::<Duration>() / count
+    }
+}
+
+pub struct FrameStatsSystem;
+
+impl FrameStatsSystem {
+    pub fn record(stats: &mut FrameStats, sample: FrameSample) {
+        if stats.samples.len() == stats.capacity {
+            stats.samples.pop_front();
+        }
+        if sample.frame_time > stats.budget {
+            stats.frames_over_budget += 1;
+        }
+        stats.frames += 1;
+        stats.samples.push_back(sample);
+    }
+
+    pub fn set_budget(stats: &mut FrameStats, budget: Duration) {
+        stats.budget = budget;
+    }
+
+    pub fn reset(stats: &mut FrameStats) {
+        stats.samples.clear();
+        stats.frames = 0;
+        stats.frames_over_budget = 0;
+    }
+
+    /// Writes the rolling window to a CSV file, one row per frame with times in milliseconds.
+    pub fn write_csv(stats: &FrameStats, path: impl AsRef<Path>) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "frame,frame_time_ms,update_time_ms,render_time_ms")?;
+        for sample in stats.samples.iter() {
+            writeln!(
+                writer,
+                "{},{:.4},{:.4},{:.4}",
+                sample.frame,
+                sample.frame_time.as_secs_f64() * 1000.0,
+                sample.update_time.as_secs_f64() * 1000.0,
+                sample.render_time.as_secs_f64() * 1000.0,
+            )?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(frame: u64, millis: u64) -> FrameSample {
+        FrameSample {
+            frame,
+            frame_time: Duration::from_millis(millis),
+            update_time: Duration::from_millis(1),
+            render_time: Duration::from_millis(2),
+        }
+    }
+
+    #[test]
+    fn rolling_window_drops_the_oldest_frames() {
+        let mut stats = FrameStats::new(3, Duration::from_millis(16));
+        for (frame, millis) in [10, 20, 30, 40].into_iter().enumerate() {
+            FrameStatsSystem::record(&mut stats, sample(frame as u64, millis));
+        }
+        assert_eq!(stats.frames(), 4);
+        assert_eq!(stats.min_frame_time(), Duration::from_millis(20));
+        assert_eq!(stats.max_frame_time(), Duration::from_millis(40));
+        assert_eq!(stats.average_frame_time(), Duration::from_millis(30));
+        assert_eq!(
+            stats.percentile_frame_time(100.0),
+            Duration::from_millis(40)
+        );
+    }
+
+    #[test]
+    fn frames_are_judged_by_the_budget_when_recorded() {
+        let mut stats = FrameStats::new(10, Duration::from_millis(16));
+        FrameStatsSystem::record(&mut stats, sample(0, 20));
+        // A background frame at 10 fps is on time
+        FrameStatsSystem::set_budget(&mut stats, Duration::from_millis(100));
+        FrameStatsSystem::record(&mut stats, sample(1, 90));
+        FrameStatsSystem::set_budget(&mut stats, Duration::from_millis(16));
+        FrameStatsSystem::record(&mut stats, sample(2, 10));
+        assert_eq!(stats.frames_over_budget(), 1);
+    }
+
+    #[test]
+    fn csv_has_a_row_per_frame() {
+        let path = std::env::temp_dir().join(format!("effect-stats-{}.csv", std::process::id()));
+        let mut stats = FrameStats::new(10, Duration::from_millis(16));
+        FrameStatsSystem::record(&mut stats, sample(0, 16));
+        FrameStatsSystem::record(&mut stats, sample(1, 17));
+        FrameStatsSystem::write_csv(&stats, &path).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2], "1,17.0000,1.0000,2.0000");
+    }
+}
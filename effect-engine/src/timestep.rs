@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+/// Accumulates frame time and hands it out in fixed sized ticks,
+/// so simulation code runs at the same rate regardless of frame rate.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedTimestep {
+    tick: Duration,
+    max_catch_up: u32,
+    accumulator: Duration,
+    alpha: f32,
+}
+
+impl FixedTimestep {
+    /// Rates above a billion ticks per second are clamped to one tick per nanosecond.
+    pub fn new(tick_rate: u32, max_catch_up: u32) -> Self {
+        let tick = (Duration::from_secs(1) / tick_rate.max(1)).max(Duration::from_nanos(1));
+        let max_catch_up = max_catch_up.max(1);
+        let accumulator = Duration::ZERO;
+        let alpha = 0.0;
+        Self {
+            tick,
+            max_catch_up,
+            accumulator,
+            alpha,
+        }
+    }
+
+    pub fn tick(&self) -> Duration {
+        self.tick
+    }
+
+    pub fn max_catch_up(&self) -> u32 {
+        self.max_catch_up
+    }
+
+    /// How far between the previous and next tick the current frame is, from 0.0 to 1.0.
+    /// Use it to interpolate between the last two simulation states when rendering.
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new(60, 5)
+    }
+}
+
+pub struct FixedTimestepSystem;
+
+impl FixedTimestepSystem {
+    /// Adds the frame delta to the accumulator and returns how many ticks should run.
+    /// If more than `max_catch_up` ticks are owed, the excess is dropped so a long stall
+    /// does not lead to a spiral of ever longer frames.
+    pub fn advance(timestep: &mut FixedTimestep, delta_time: Duration) -> u32 {
+        timestep.accumulator += delta_time;
+        let owed = timestep.accumulator.as_nanos() / timestep.tick.as_nanos();
+        let ticks = owed.min(timestep.max_catch_up as u128) as u32;
+        timestep.accumulator -= timestep.tick * ticks;
+        if timestep.accumulator >= timestep.tick {
+            let remainder = timestep.accumulator.as_nanos() % timestep.tick.as_nanos();
+            timestep.accumulator = Duration::from_nanos(remainder as u64);
+        }
+        timestep.alpha = timestep.accumulator.as_secs_f32() / timestep.tick.as_secs_f32();
+        ticks
+    }
+
+    pub fn reset(timestep: &mut FixedTimestep) {
+        timestep.accumulator = Duration::ZERO;
+        timestep.alpha = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn tick_comes_from_the_rate() {
+        assert_eq!(FixedTimestep::new(100, 5).tick(), TICK);
+        assert_eq!(FixedTimestep::new(0, 5).tick(), Duration::from_secs(1));
+        assert_eq!(
+            FixedTimestep::new(u32::MAX, 5).tick(),
+            Duration::from_nanos(1)
+        );
+        assert_eq!(FixedTimestep::new(60, 0).max_catch_up(), 1);
+    }
+
+    #[test]
+    fn short_frames_accumulate_into_a_tick() {
+        let mut timestep = FixedTimestep::new(100, 5);
+        assert_eq!(
+            FixedTimestepSystem::advance(&mut timestep, Duration::from_millis(4)),
+            0
+        );
+        assert_eq!(
+            FixedTimestepSystem::advance(&mut timestep, Duration::from_millis(4)),
+            0
+        );
+        assert_eq!(
+            FixedTimestepSystem::advance(&mut timestep, Duration::from_millis(4)),
+            1
+        );
+        assert_eq!(timestep.accumulator, Duration::from_millis(2));
+    }
+
+    #[test]
+    fn long_frames_run_several_ticks() {
+        let mut timestep = FixedTimestep::new(100, 5);
+        assert_eq!(
+            FixedTimestepSystem::advance(&mut timestep, Duration::from_millis(35)),
+            3
+        );
+        assert_eq!(timestep.accumulator, Duration::from_millis(5));
+        assert_eq!(
+            FixedTimestepSystem::advance(&mut timestep, Duration::from_millis(5)),
+            1
+        );
+        assert_eq!(timestep.accumulator, Duration::ZERO);
+    }
+
+    #[test]
+    fn stalls_are_clamped_to_max_catch_up() {
+        let mut timestep = FixedTimestep::new(100, 5);
+        assert_eq!(
+            FixedTimestepSystem::advance(&mut timestep, Duration::from_secs(2)),
+            5
+        );
+        assert_eq!(
+            FixedTimestepSystem::advance(&mut timestep, Duration::ZERO),
+            0
+        );
+    }
+
+    #[test]
+    fn dropped_time_keeps_the_partial_tick() {
+        let mut timestep = FixedTimestep::new(100, 2);
+        // 2 ticks run, 5 are dropped and the 3ms into the next tick is kept
+        assert_eq!(
+            FixedTimestepSystem::advance(&mut timestep, Duration::from_millis(73)),
+            2
+        );
+        assert_eq!(timestep.accumulator, Duration::from_millis(3));
+        assert!(timestep.accumulator < timestep.tick());
+    }
+
+    #[test]
+    fn alpha_is_the_fraction_of_the_next_tick() {
+        let mut timestep = FixedTimestep::new(100, 5);
+        FixedTimestepSystem::advance(&mut timestep, Duration::from_millis(25));
+        assert!((timestep.alpha() - 0.5).abs() < 1e-6);
+        FixedTimestepSystem::advance(&mut timestep, Duration::from_millis(4));
+        assert!((timestep.alpha() - 0.9).abs() < 1e-6);
+        FixedTimestepSystem::advance(&mut timestep, Duration::from_millis(1));
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn alpha_stays_below_one_after_a_stall() {
+        let mut timestep = FixedTimestep::new(100, 1);
+        FixedTimestepSystem::advance(&mut timestep, Duration::from_millis(58));
+        assert!(timestep.alpha() >= 0.0 && timestep.alpha() < 1.0);
+        assert!((timestep.alpha() - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn reset_clears_the_accumulator() {
+        let mut timestep = FixedTimestep::new(100, 5);
+        FixedTimestepSystem::advance(&mut timestep, Duration::from_millis(7));
+        FixedTimestepSystem::reset(&mut timestep);
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(
+            FixedTimestepSystem::advance(&mut timestep, Duration::from_millis(7)),
+            0
+        );
+    }
+}
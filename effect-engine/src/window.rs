@@ -0,0 +1,25 @@
+use effect_core::misc::window_info::WindowInfo;
+use effect_events::input::EffectEvent;
+
+/// Names an additional window opened through `EffectControl::open_window`.
+/// The main window is not named, hooks without a window ID refer to it.
+#[derive(Hash, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct WindowID(pub &'static str);
+
+pub(crate) enum WindowRequest {
+    Open(WindowID, WindowInfo),
+    Close(WindowID),
+}
+
+/// An additional window, with its own engine and input state.
+pub(crate) struct EffectWindow2D<E> {
+    pub engine: E,
+    pub event: EffectEvent,
+}
+
+impl<E> EffectWindow2D<E> {
+    pub fn new(engine: E) -> Self {
+        let event = EffectEvent::new();
+        Self { engine, event }
+    }
+}
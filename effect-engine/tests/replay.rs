@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use effect_engine::app::EffectApp;
+use effect_engine::control::{EffectControl, ExitReason};
+use effect_engine::events::input::gamepad::{
+    GamepadAxis, GamepadButton, GamepadID, GamepadStick, MockGamepadBackend,
+};
+use effect_engine::events::input::record::InputRecordingSystem;
+use effect_engine::events::input::EffectEvent;
+use effect_engine::headless::{EffectHeadlessLoop, HeadlessEngine2D};
+use effect_engine::EffectAppBuilder;
+
+const PAD: GamepadID = GamepadID(0);
+const FRAMES: u64 = 40;
+
+/// Everything the game derives from its input, compared between the two runs.
+#[derive(Debug, Default, PartialEq)]
+struct GameState {
+    ticks: u32,
+    position: (f32, f32),
+    presses: Vec<u64>,
+    connected: Vec<u64>,
+    disconnected: Vec<u64>,
+    sticks: Vec<(f32, f32)>,
+    frame_times: Vec<Duration>,
+}
+
+/// Moves a point with the left stick on each fixed tick and counts button presses.
+/// While recording, input is scripted through the mock backend a frame ahead,
+/// when replaying the same pushes are ignored.
+struct GamepadGame {
+    injector: MockGamepadBackend,
+    state: GameState,
+}
+
+impl GamepadGame {
+    fn new(injector: MockGamepadBackend) -> Self {
+        let state = GameState::default();
+        Self { injector, state }
+    }
+
+    fn script(&self, frame: u64) {
+        match frame {
+            0 => self.injector.connect(PAD, "Pad"),
+            4 => self.injector.set_axis(PAD, GamepadAxis::LeftStickX, 0.8),
+            9 => self.injector.press(PAD, GamepadButton::South),
+            11 => self.injector.release(PAD, GamepadButton::South),
+            14 => self.injector.set_axis(PAD, GamepadAxis::LeftStickY, -0.5),
+            20 => self.injector.press(PAD, GamepadButton::South),
+            24 => self.injector.set_axis(PAD, GamepadAxis::LeftStickX, 0.05),
+            30 => self.injector.disconnect(PAD),
+            _ => (),
+        }
+    }
+}
+
+impl EffectApp<HeadlessEngine2D> for GamepadGame {
+    fn fixed_update(
+        &mut self,
+        ctx: &mut EffectEvent,
+        tick: Duration,
+        _control: &mut EffectControl,
+        _engine: &mut HeadlessEngine2D,
+    ) {
+        let (x, y) = ctx.gamepad_stick(PAD, GamepadStick::Left);
+        self.state.position.0 += x * tick.as_secs_f32();
+        self.state.position.1 += y * tick.as_secs_f32();
+        self.state.ticks += 1;
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut EffectEvent,
+        delta_time: Duration,
+        control: &mut EffectControl,
+        _engine: &mut HeadlessEngine2D,
+    ) {
+        let frame = control.frame();
+        if ctx.is_gamepad_just_connected(PAD) {
+            self.state.connected.push(frame);
+        }
+        if ctx.is_gamepad_just_disconnected(PAD) {
+            self.state.disconnected.push(frame);
+        }
+        if ctx.is_gamepad_button_just_pressed(PAD, GamepadButton::South) {
+            self.state.presses.push(frame);
+        }
+        self.state
+            .sticks
+            .push(ctx.gamepad_stick(PAD, GamepadStick::Left));
+        self.state.frame_times.push(delta_time);
+        self.script(frame);
+    }
+}
+
+fn headless_loop() -> EffectHeadlessLoop {
+    EffectAppBuilder::default()
+        .headless(true)
+        .tick_rate(60)
+        .build()
+        .get_headless()
+}
+
+#[test]
+fn replay_reproduces_a_recorded_gamepad_run() {
+    let path = std::env::temp_dir().join(format!("effect-replay-{}.bin", std::process::id()));
+    let backend = MockGamepadBackend::new();
+    let mut recorded = GamepadGame::new(backend.clone());
+    // An uneven frame time, so ticks only line up if the replay uses the recorded times
+    let reason = headless_loop()
+        .frame_time(Duration::from_millis(23))
+        .frame_limit(FRAMES)
+        .gamepad_backend(Box::new(backend))
+        .record_input(&path)
+        .run(&mut recorded)
+        .unwrap();
+    assert_eq!(reason, ExitReason::FrameLimit);
+
+    let recording = InputRecordingSystem::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(recording.frames(), FRAMES);
+
+    let mut replayed = GamepadGame::new(MockGamepadBackend::new());
+    let reason = headless_loop()
+        .replay_input(recording)
+        .run(&mut replayed)
+        .unwrap();
+    assert_eq!(reason, ExitReason::ReplayFinished);
+
+    // Check the script really ran, so an empty recording can't pass
+    assert_eq!(recorded.state.connected, vec![1]);
+    assert_eq!(recorded.state.presses, vec![10, 21]);
+    assert_eq!(recorded.state.disconnected, vec![31]);
+    assert!(recorded.state.position.0 > 0.0 && recorded.state.position.1 < 0.0);
+    assert_eq!(replayed.state, recorded.state);
+}
@@ -1,56 +1,298 @@
 use std::{
     collections::{HashMap, HashSet},
+    path::Path,
     time::Duration,
 };
 
+use anyhow::Result;
+
 use effect_core::{
-    camera::camera2d::{Camera2D, CameraAction},
+    camera::camera2d::{Camera2D, Camera2DSystem, CameraAction},
     primitives::vector::Vector3,
 };
-use winit::keyboard::KeyCode;
+use serde::{Serialize, Serializer};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    keyboard::KeyCode,
+};
 
+use super::input_map::{Binding, InputMap, InputMapSystem};
 use super::EffectEvent;
 
+/// The position and matrices of a 2D camera. The camera systems work through this
+/// rather than `Camera2D` itself, so they also drive the headless engine's camera.
+pub trait CameraTransform2D {
+    fn position(&self) -> Vector3<f32>;
+
+    fn position_mut(&mut self) -> &mut Vector3<f32>;
+
+    fn proj(&self) -> glam::Mat4;
+
+    fn set_proj(&mut self, proj: glam::Mat4);
+
+    fn look_at(&self) -> glam::Mat4;
+
+    fn set_look_at(&mut self, look_at: glam::Mat4);
+}
+
+impl CameraTransform2D for Camera2D {
+    fn position(&self) -> Vector3<f32> {
+        self.position
+    }
+
+    fn position_mut(&mut self) -> &mut Vector3<f32> {
+        &mut self.position
+    }
+
+    fn proj(&self) -> glam::Mat4 {
+        self.proj
+    }
+
+    fn set_proj(&mut self, proj: glam::Mat4) {
+        self.proj = proj;
+    }
+
+    fn look_at(&self) -> glam::Mat4 {
+        self.look_at
+    }
+
+    fn set_look_at(&mut self, look_at: glam::Mat4) {
+        self.look_at = look_at;
+    }
+}
+
+// Serde names for camera actions, which come from effect_core without a Serialize impl
+#[derive(Serialize)]
+#[serde(remote = "CameraAction")]
+enum CameraActionDef {
+    Up,
+    Down,
+    Left,
+    Right,
+    ZoomIn,
+    ZoomOut,
+}
+
+struct CameraActionName<'a>(&'a CameraAction);
+
+impl Serialize for CameraActionName<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CameraActionDef::serialize(self.0, serializer)
+    }
+}
+
 pub struct CameraUpdateSystem2D;
 
 impl CameraUpdateSystem2D {
+    /// Replaces the camera's inputs with the first key bound to each camera action,
+    /// so camera controls can be rebound and saved like any other `InputMap`.
+    /// The camera only reads the keyboard, other bindings are skipped.
+    pub fn set_bindings(camera: &mut Camera2D, map: &InputMap<CameraAction>) {
+        camera.key_codes.clear();
+        camera.current_actions.clear();
+        Camera2DSystem::set_inputs(camera, &Self::key_bindings(map));
+    }
+
+    /// `InputMapSystem::save` for camera bindings.
+    pub fn save_bindings(map: &InputMap<CameraAction>, path: impl AsRef<Path>) -> Result<()> {
+        InputMapSystem::save_with(map, path, |action| {
+            InputMapSystem::action_name(&CameraActionName(action))
+        })
+    }
+
+    /// `InputMapSystem::load` for camera bindings.
+    pub fn load_bindings(map: &mut InputMap<CameraAction>, path: impl AsRef<Path>) -> Result<()> {
+        InputMapSystem::load_with(map, path, |action| {
+            InputMapSystem::action_name(&CameraActionName(action))
+        })
+    }
+
+    /// The first key bound to each camera action.
+    pub fn key_bindings(map: &InputMap<CameraAction>) -> Vec<(CameraAction, KeyCode)> {
+        let actions = [
+            CameraAction::Up,
+            CameraAction::Down,
+            CameraAction::Left,
+            CameraAction::Right,
+            CameraAction::ZoomIn,
+            CameraAction::ZoomOut,
+        ];
+        actions
+            .into_iter()
+            .filter_map(|action| {
+                map.bindings(action)
+                    .iter()
+                    .find_map(|binding| match binding {
+                        Binding::Key(key) => Some((action, *key)),
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
     pub fn update(camera: &mut Camera2D, ctx: &EffectEvent, delta_time: Duration) {
-        for (camera_action, key_code) in camera.key_codes.iter() {
+        Self::update_actions(&camera.key_codes, &mut camera.current_actions, ctx);
+        Self::move_position(
+            &mut camera.position,
+            &camera.current_actions,
+            camera.speed,
+            delta_time,
+        );
+        Self::update_view(camera);
+    }
+
+    /// Starts and stops camera actions from the keys pressed and released this frame.
+    pub fn update_actions(
+        key_codes: &HashMap<CameraAction, KeyCode>,
+        current_actions: &mut HashSet<CameraAction>,
+        ctx: &EffectEvent,
+    ) {
+        for (camera_action, key_code) in key_codes.iter() {
             if ctx.is_key_pressed(*key_code) {
-                camera.current_actions.insert(*camera_action);
+                current_actions.insert(*camera_action);
             }
             if ctx.is_key_released(*key_code) {
-                camera.current_actions.remove(camera_action);
+                current_actions.remove(camera_action);
             }
         }
+    }
+
+    /// Moves a camera position for each held action, `speed` is in units per millisecond.
+    pub fn move_position(
+        position: &mut Vector3<f32>,
+        current_actions: &HashSet<CameraAction>,
+        speed: f32,
+        delta_time: Duration,
+    ) {
         let dt = delta_time.as_micros() as f32 / 1000.0;
-        for action in camera.current_actions.iter() {
+        for action in current_actions.iter() {
             match action {
                 CameraAction::Up => {
-                    camera.position.y += camera.speed * dt;
+                    position.y += speed * dt;
                 }
                 CameraAction::Down => {
-                    camera.position.y -= camera.speed * dt;
+                    position.y -= speed * dt;
                 }
                 CameraAction::Right => {
-                    camera.position.x += camera.speed * dt;
+                    position.x += speed * dt;
                 }
                 CameraAction::Left => {
-                    camera.position.x -= camera.speed * dt;
+                    position.x -= speed * dt;
                 }
                 CameraAction::ZoomIn => {
-                    camera.position.z -= camera.speed * dt;
+                    position.z -= speed * dt;
                 }
                 CameraAction::ZoomOut => {
-                    camera.position.z += camera.speed * dt;
+                    position.z += speed * dt;
                 }
             }
         }
+    }
 
-        camera.look_at = glam::Mat4::look_at_rh(
-            glam::Vec3::new(camera.position.x, camera.position.y, camera.position.z),
-            glam::Vec3::new(camera.position.x, camera.position.y, 0.0),
+    /// Rebuilds the view matrix after the camera's position has changed.
+    pub fn update_view<C: CameraTransform2D>(camera: &mut C) {
+        let position = camera.position();
+        camera.set_look_at(glam::Mat4::look_at_rh(
+            glam::Vec3::new(position.x, position.y, position.z),
+            glam::Vec3::new(position.x, position.y, 0.0),
             glam::Vec3::Y,
+        ));
+    }
+}
+
+/// Conversions between window pixels and world space through a camera's
+/// projection and view, so clicks can be matched to entities.
+pub struct CameraProjectionSystem2D;
+
+impl CameraProjectionSystem2D {
+    /// The world point under a window pixel, on the plane at depth `z`.
+    /// `None` when the window has no area or the plane is edge on to the view.
+    pub fn screen_to_world<C: CameraTransform2D>(
+        camera: &C,
+        window_size: PhysicalSize<u32>,
+        screen: PhysicalPosition<f64>,
+        z: f32,
+    ) -> Option<glam::Vec3> {
+        if window_size.width == 0 || window_size.height == 0 {
+            return None;
+        }
+        let ndc_x = (2.0 * screen.x / window_size.width as f64 - 1.0) as f32;
+        let ndc_y = (1.0 - 2.0 * screen.y / window_size.height as f64) as f32;
+        // Cast a ray from the near to the far plane and see where it crosses z
+        let inverse = (camera.proj() * camera.look_at()).inverse();
+        let near = inverse.project_point3(glam::Vec3::new(ndc_x, ndc_y, 0.0));
+        let far = inverse.project_point3(glam::Vec3::new(ndc_x, ndc_y, 1.0));
+        let direction = far - near;
+        if direction.z.abs() <= f32::EPSILON {
+            return None;
+        }
+        let t = (z - near.z) / direction.z;
+        Some(near + direction * t)
+    }
+
+    /// The window pixel a world point is drawn at, which may be outside the window.
+    /// `None` when the point is behind the camera or the window has no area.
+    pub fn world_to_screen<C: CameraTransform2D>(
+        camera: &C,
+        window_size: PhysicalSize<u32>,
+        world: glam::Vec3,
+    ) -> Option<PhysicalPosition<f64>> {
+        if window_size.width == 0 || window_size.height == 0 {
+            return None;
+        }
+        let clip = camera.proj() * camera.look_at() * world.extend(1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let ndc = clip.truncate() / clip.w;
+        let x = (ndc.x as f64 + 1.0) / 2.0 * window_size.width as f64;
+        let y = (1.0 - ndc.y as f64) / 2.0 * window_size.height as f64;
+        Some(PhysicalPosition::new(x, y))
+    }
+
+    /// The world point under the mouse on the plane at depth `z`,
+    /// using the window size tracked by `ctx` so resizes are accounted for.
+    pub fn mouse_to_world<C: CameraTransform2D>(
+        camera: &C,
+        ctx: &EffectEvent,
+        z: f32,
+    ) -> Option<glam::Vec3> {
+        Self::screen_to_world(camera, ctx.window_size(), ctx.mouse_position(), z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_bindings_save_under_the_variant_names() {
+        let path = std::env::temp_dir().join(format!(
+            "effect-camera-bindings-{}.toml",
+            std::process::id()
+        ));
+        let mut saved = InputMap::new();
+        saved.bind(CameraAction::ZoomIn, Binding::Key(KeyCode::KeyQ));
+        saved.bind(CameraAction::Left, Binding::Key(KeyCode::KeyA));
+        CameraUpdateSystem2D::save_bindings(&saved, &path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains("ZoomIn") && text.contains("Left"), "{text}");
+
+        let mut loaded = InputMap::new();
+        loaded.bind(CameraAction::ZoomIn, Binding::Key(KeyCode::KeyE));
+        loaded.bind(CameraAction::Left, Binding::Key(KeyCode::ArrowLeft));
+        CameraUpdateSystem2D::load_bindings(&mut loaded, &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            loaded.bindings(CameraAction::ZoomIn),
+            &[Binding::Key(KeyCode::KeyQ)]
+        );
+        assert_eq!(
+            CameraUpdateSystem2D::key_bindings(&loaded),
+            vec![
+                (CameraAction::Left, KeyCode::KeyA),
+                (CameraAction::ZoomIn, KeyCode::KeyQ)
+            ]
         );
     }
 }
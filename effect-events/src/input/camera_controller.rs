@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use super::camera2d::{CameraTransform2D, CameraUpdateSystem2D};
+
+/// Behaviours layered on top of a `Camera2D`: following a target, world bounds,
+/// zoom limits and screen shake. Run `CameraControllerSystem2D::update` after anything
+/// else that moves the camera, such as key driven `CameraAction`s, each frame.
+pub struct CameraController2D {
+    target: Option<glam::Vec2>,
+    dead_zone: glam::Vec2,
+    follow_speed: f32,
+    bounds: Option<(glam::Vec2, glam::Vec2)>,
+    zoom_limits: Option<(f32, f32)>,
+    trauma: f32,
+    trauma_decay: f32,
+    max_shake: glam::Vec2,
+    shake_offset: glam::Vec2,
+    shake_time: f32,
+}
+
+impl CameraController2D {
+    pub fn new() -> Self {
+        let target = None;
+        let dead_zone = glam::Vec2::ZERO;
+        let follow_speed = 5.0;
+        let bounds = None;
+        let zoom_limits = None;
+        let trauma = 0.0;
+        let trauma_decay = 1.0;
+        let max_shake = glam::Vec2::splat(0.5);
+        let shake_offset = glam::Vec2::ZERO;
+        let shake_time = 0.0;
+        Self {
+            target,
+            dead_zone,
+            follow_speed,
+            bounds,
+            zoom_limits,
+            trauma,
+            trauma_decay,
+            max_shake,
+            shake_offset,
+            shake_time,
+        }
+    }
+
+    pub fn target(&self) -> Option<glam::Vec2> {
+        self.target
+    }
+
+    /// From 0.0 to 1.0, shake strength grows with its square.
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+}
+
+impl Default for CameraController2D {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct CameraControllerSystem2D;
+
+impl CameraControllerSystem2D {
+    /// World position to keep in view, usually an entity's position updated every frame.
+    /// `None` stops following and leaves the camera where it is.
+    pub fn follow(controller: &mut CameraController2D, target: Option<glam::Vec2>) {
+        controller.target = target;
+    }
+
+    /// Half the size of the box around the camera's centre the target can move in
+    /// without the camera moving.
+    pub fn set_dead_zone(controller: &mut CameraController2D, half_extents: glam::Vec2) {
+        controller.dead_zone = half_extents.abs();
+    }
+
+    /// How quickly the camera catches up with the target, higher is snappier.
+    /// Zero snaps straight to it. Smoothing is the same at any frame rate.
+    pub fn set_follow_speed(controller: &mut CameraController2D, speed: f32) {
+        controller.follow_speed = speed.max(0.0);
+    }
+
+    /// Keeps the camera's centre within the rectangle.
+    pub fn set_bounds(controller: &mut CameraController2D, min: glam::Vec2, max: glam::Vec2) {
+        controller.bounds = Some((min.min(max), min.max(max)));
+    }
+
+    pub fn clear_bounds(controller: &mut CameraController2D) {
+        controller.bounds = None;
+    }
+
+    /// Keeps the camera's distance from the scene, its z position, within the range.
+    pub fn set_zoom_limits(controller: &mut CameraController2D, min: f32, max: f32) {
+        controller.zoom_limits = Some((min.min(max), min.max(max)));
+    }
+
+    pub fn clear_zoom_limits(controller: &mut CameraController2D) {
+        controller.zoom_limits = None;
+    }
+
+    /// Largest shake offset in world units at full trauma, and trauma lost per second.
+    pub fn set_shake(controller: &mut CameraController2D, max_offset: glam::Vec2, decay: f32) {
+        controller.max_shake = max_offset.abs();
+        controller.trauma_decay = decay.max(0.0);
+    }
+
+    /// Adds to the shake, capped at 1.0. Hits can add a little, explosions a lot.
+    pub fn add_trauma(controller: &mut CameraController2D, amount: f32) {
+        controller.trauma = (controller.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    pub fn update<C: CameraTransform2D>(
+        controller: &mut CameraController2D,
+        camera: &mut C,
+        delta_time: Duration,
+    ) {
+        let dt = delta_time.as_secs_f32();
+        // Take last frame's shake back out, so it never builds up in the position
+        let position = camera.position_mut();
+        let mut centre = glam::Vec2::new(position.x, position.y) - controller.shake_offset;
+
+        if let Some(target) = controller.target {
+            let desired = Self::dead_zone_centre(centre, target, controller.dead_zone);
+            centre = if controller.follow_speed == 0.0 {
+                desired
+            } else {
+                let t = 1.0 - (-controller.follow_speed * dt).exp();
+                centre.lerp(desired, t)
+            };
+        }
+        if let Some((min, max)) = controller.bounds {
+            centre = centre.clamp(min, max);
+        }
+        if let Some((min, max)) = controller.zoom_limits {
+            position.z = position.z.clamp(min, max);
+        }
+
+        controller.trauma = (controller.trauma - controller.trauma_decay * dt).max(0.0);
+        controller.shake_time += dt;
+        let strength = controller.trauma * controller.trauma;
+        // Sums of sines rather than random numbers, so replays shake the same way
+        let t = controller.shake_time;
+        let noise = glam::Vec2::new(
+            (t * 37.0).sin() * 0.6 + (t * 83.0).sin() * 0.4,
+            (t * 41.0 + 1.7).sin() * 0.6 + (t * 79.0 + 0.3).sin() * 0.4,
+        );
+        controller.shake_offset = controller.max_shake * strength * noise;
+
+        let shaken = centre + controller.shake_offset;
+        position.x = shaken.x;
+        position.y = shaken.y;
+        CameraUpdateSystem2D::update_view(camera);
+    }
+
+    // Moves the centre only as far as needed to bring the target back inside the dead zone
+    fn dead_zone_centre(
+        centre: glam::Vec2,
+        target: glam::Vec2,
+        dead_zone: glam::Vec2,
+    ) -> glam::Vec2 {
+        let offset = target - centre;
+        let excess = offset - offset.clamp(-dead_zone, dead_zone);
+        centre + excess
+    }
+}
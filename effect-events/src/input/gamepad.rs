@@ -0,0 +1,534 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "gilrs")]
+use anyhow::Result;
+#[cfg(feature = "gilrs")]
+use effect_util::effect_error::EffectError;
+
+/// Player index of a connected gamepad, the first controller connected is 0.
+/// Indices are reused once a controller disconnects.
+#[derive(Hash, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
+pub struct GamepadID(pub usize);
+
+/// Buttons named by position, South is A on Xbox layouts and Cross on PlayStation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// Sticks range from -1.0 to 1.0 with up being positive, triggers from 0.0 to 1.0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadStick {
+    Left,
+    Right,
+}
+
+impl GamepadStick {
+    pub fn axes(&self) -> (GamepadAxis, GamepadAxis) {
+        match self {
+            GamepadStick::Left => (GamepadAxis::LeftStickX, GamepadAxis::LeftStickY),
+            GamepadStick::Right => (GamepadAxis::RightStickX, GamepadAxis::RightStickY),
+        }
+    }
+}
+
+impl GamepadAxis {
+    pub fn stick(&self) -> Option<GamepadStick> {
+        match self {
+            GamepadAxis::LeftStickX | GamepadAxis::LeftStickY => Some(GamepadStick::Left),
+            GamepadAxis::RightStickX | GamepadAxis::RightStickY => Some(GamepadStick::Right),
+            GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => None,
+        }
+    }
+}
+
+/// Raw controller input as reported by a `GamepadBackend`, before dead zones are applied.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GamepadEvent {
+    Connected(GamepadID, String),
+    Disconnected(GamepadID),
+    ButtonPressed(GamepadID, GamepadButton),
+    ButtonReleased(GamepadID, GamepadButton),
+    AxisChanged(GamepadID, GamepadAxis, f32),
+}
+
+/// Source of gamepad events, polled once per frame by the main loop
+/// and fed to `EffectEventSystem::gamepad_event_update`.
+pub trait GamepadBackend {
+    /// Events since the last poll, oldest first.
+    fn poll(&mut self) -> Vec<GamepadEvent>;
+}
+
+/// Inputs below the dead zone read as zero, the rest of the range is rescaled
+/// so values still start from zero. Sticks use a radial dead zone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GamepadDeadZones {
+    pub stick: f32,
+    pub trigger: f32,
+}
+
+impl Default for GamepadDeadZones {
+    fn default() -> Self {
+        let stick = 0.15;
+        let trigger = 0.05;
+        Self { stick, trigger }
+    }
+}
+
+pub(crate) struct GamepadState {
+    pub name: String,
+    pub buttons_pressed: HashSet<GamepadButton>,
+    pub buttons_just_pressed: HashSet<GamepadButton>,
+    pub buttons_released: HashSet<GamepadButton>,
+    pub left_stick: (f32, f32),
+    pub right_stick: (f32, f32),
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+}
+
+impl GamepadState {
+    pub fn new(name: String) -> Self {
+        let buttons_pressed = HashSet::new();
+        let buttons_just_pressed = HashSet::new();
+        let buttons_released = HashSet::new();
+        let left_stick = (0.0, 0.0);
+        let right_stick = (0.0, 0.0);
+        let left_trigger = 0.0;
+        let right_trigger = 0.0;
+        Self {
+            name,
+            buttons_pressed,
+            buttons_just_pressed,
+            buttons_released,
+            left_stick,
+            right_stick,
+            left_trigger,
+            right_trigger,
+        }
+    }
+
+    pub fn set_axis(&mut self, axis: GamepadAxis, value: f32) {
+        match axis {
+            GamepadAxis::LeftStickX => self.left_stick.0 = value.clamp(-1.0, 1.0),
+            GamepadAxis::LeftStickY => self.left_stick.1 = value.clamp(-1.0, 1.0),
+            GamepadAxis::RightStickX => self.right_stick.0 = value.clamp(-1.0, 1.0),
+            GamepadAxis::RightStickY => self.right_stick.1 = value.clamp(-1.0, 1.0),
+            GamepadAxis::LeftTrigger => self.left_trigger = value.clamp(0.0, 1.0),
+            GamepadAxis::RightTrigger => self.right_trigger = value.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn stick(&self, stick: GamepadStick, dead_zones: GamepadDeadZones) -> (f32, f32) {
+        let (x, y) = match stick {
+            GamepadStick::Left => self.left_stick,
+            GamepadStick::Right => self.right_stick,
+        };
+        let magnitude = (x * x + y * y).sqrt();
+        if magnitude <= dead_zones.stick {
+            return (0.0, 0.0);
+        }
+        let scaled = ((magnitude - dead_zones.stick) / (1.0 - dead_zones.stick)).min(1.0);
+        (x / magnitude * scaled, y / magnitude * scaled)
+    }
+
+    pub fn axis(&self, axis: GamepadAxis, dead_zones: GamepadDeadZones) -> f32 {
+        let trigger = match axis {
+            GamepadAxis::LeftTrigger => self.left_trigger,
+            GamepadAxis::RightTrigger => self.right_trigger,
+            GamepadAxis::LeftStickX | GamepadAxis::RightStickX => {
+                return self.stick(axis.stick().unwrap(), dead_zones).0;
+            }
+            GamepadAxis::LeftStickY | GamepadAxis::RightStickY => {
+                return self.stick(axis.stick().unwrap(), dead_zones).1;
+            }
+        };
+        if trigger <= dead_zones.trigger {
+            return 0.0;
+        }
+        ((trigger - dead_zones.trigger) / (1.0 - dead_zones.trigger)).min(1.0)
+    }
+}
+
+/// Backend driven by hand, for tests and replays without controller hardware.
+/// Clones share the same queue, keep one to inject input while the loop owns another.
+#[derive(Clone, Default)]
+pub struct MockGamepadBackend {
+    events: Rc<RefCell<Vec<GamepadEvent>>>,
+}
+
+impl MockGamepadBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues an event for the next poll.
+    pub fn push(&self, event: GamepadEvent) {
+        self.events.borrow_mut().push(event);
+    }
+
+    pub fn connect(&self, id: GamepadID, name: &str) {
+        self.push(GamepadEvent::Connected(id, String::from(name)));
+    }
+
+    pub fn disconnect(&self, id: GamepadID) {
+        self.push(GamepadEvent::Disconnected(id));
+    }
+
+    pub fn press(&self, id: GamepadID, button: GamepadButton) {
+        self.push(GamepadEvent::ButtonPressed(id, button));
+    }
+
+    pub fn release(&self, id: GamepadID, button: GamepadButton) {
+        self.push(GamepadEvent::ButtonReleased(id, button));
+    }
+
+    pub fn set_axis(&self, id: GamepadID, axis: GamepadAxis, value: f32) {
+        self.push(GamepadEvent::AxisChanged(id, axis, value));
+    }
+}
+
+impl GamepadBackend for MockGamepadBackend {
+    fn poll(&mut self) -> Vec<GamepadEvent> {
+        std::mem::take(&mut *self.events.borrow_mut())
+    }
+}
+
+/// Backend reading real controllers through gilrs, which handles hot-plugging
+/// and maps controllers to a common layout.
+#[cfg(feature = "gilrs")]
+pub struct GilrsGamepadBackend {
+    gilrs: gilrs::Gilrs,
+    players: Vec<Option<gilrs::GamepadId>>,
+}
+
+#[cfg(feature = "gilrs")]
+impl GilrsGamepadBackend {
+    pub fn new() -> Result<Self> {
+        let gilrs = gilrs::Gilrs::new()
+            .map_err(|e| EffectError::new(&format!("Could not start gamepad support: {e}")))?;
+        let players = Vec::new();
+        Ok(Self { gilrs, players })
+    }
+
+    fn player(&self, gamepad: gilrs::GamepadId) -> Option<GamepadID> {
+        self.players
+            .iter()
+            .position(|player| *player == Some(gamepad))
+            .map(GamepadID)
+    }
+
+    // Takes the lowest free player index
+    fn connect(&mut self, gamepad: gilrs::GamepadId) -> GamepadEvent {
+        let index = match self.players.iter().position(|player| player.is_none()) {
+            Some(index) => index,
+            None => {
+                self.players.push(None);
+                self.players.len() - 1
+            }
+        };
+        self.players[index] = Some(gamepad);
+        let name = String::from(self.gilrs.gamepad(gamepad).name());
+        GamepadEvent::Connected(GamepadID(index), name)
+    }
+
+    fn button(button: gilrs::Button) -> Option<GamepadButton> {
+        Some(match button {
+            gilrs::Button::South => GamepadButton::South,
+            gilrs::Button::East => GamepadButton::East,
+            gilrs::Button::North => GamepadButton::North,
+            gilrs::Button::West => GamepadButton::West,
+            gilrs::Button::LeftTrigger => GamepadButton::LeftBumper,
+            gilrs::Button::RightTrigger => GamepadButton::RightBumper,
+            gilrs::Button::LeftTrigger2 => GamepadButton::LeftTrigger,
+            gilrs::Button::RightTrigger2 => GamepadButton::RightTrigger,
+            gilrs::Button::Select => GamepadButton::Select,
+            gilrs::Button::Start => GamepadButton::Start,
+            gilrs::Button::Mode => GamepadButton::Mode,
+            gilrs::Button::LeftThumb => GamepadButton::LeftStick,
+            gilrs::Button::RightThumb => GamepadButton::RightStick,
+            gilrs::Button::DPadUp => GamepadButton::DPadUp,
+            gilrs::Button::DPadDown => GamepadButton::DPadDown,
+            gilrs::Button::DPadLeft => GamepadButton::DPadLeft,
+            gilrs::Button::DPadRight => GamepadButton::DPadRight,
+            _ => return None,
+        })
+    }
+
+    fn axis(axis: gilrs::Axis) -> Option<GamepadAxis> {
+        Some(match axis {
+            gilrs::Axis::LeftStickX => GamepadAxis::LeftStickX,
+            gilrs::Axis::LeftStickY => GamepadAxis::LeftStickY,
+            gilrs::Axis::RightStickX => GamepadAxis::RightStickX,
+            gilrs::Axis::RightStickY => GamepadAxis::RightStickY,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(feature = "gilrs")]
+impl GamepadBackend for GilrsGamepadBackend {
+    fn poll(&mut self) -> Vec<GamepadEvent> {
+        let mut events = Vec::new();
+        // Controllers plugged in before startup never send Connected
+        let connected: Vec<gilrs::GamepadId> = self.gilrs.gamepads().map(|(id, _)| id).collect();
+        for gamepad in connected {
+            if self.player(gamepad).is_none() {
+                events.push(self.connect(gamepad));
+            }
+        }
+        while let Some(gilrs::Event { id, event, .. }) = self.gilrs.next_event() {
+            if let gilrs::EventType::Connected = event {
+                if self.player(id).is_none() {
+                    events.push(self.connect(id));
+                }
+                continue;
+            }
+            let Some(player) = self.player(id) else {
+                continue;
+            };
+            match event {
+                gilrs::EventType::Disconnected => {
+                    self.players[player.0] = None;
+                    events.push(GamepadEvent::Disconnected(player));
+                }
+                gilrs::EventType::ButtonPressed(button, _) => {
+                    if let Some(button) = Self::button(button) {
+                        events.push(GamepadEvent::ButtonPressed(player, button));
+                    }
+                }
+                gilrs::EventType::ButtonReleased(button, _) => {
+                    if let Some(button) = Self::button(button) {
+                        events.push(GamepadEvent::ButtonReleased(player, button));
+                    }
+                }
+                // gilrs reports analog triggers as buttons with a value
+                gilrs::EventType::ButtonChanged(gilrs::Button::LeftTrigger2, value, _) => {
+                    events.push(GamepadEvent::AxisChanged(
+                        player,
+                        GamepadAxis::LeftTrigger,
+                        value,
+                    ));
+                }
+                gilrs::EventType::ButtonChanged(gilrs::Button::RightTrigger2, value, _) => {
+                    events.push(GamepadEvent::AxisChanged(
+                        player,
+                        GamepadAxis::RightTrigger,
+                        value,
+                    ));
+                }
+                gilrs::EventType::AxisChanged(axis, value, _) => {
+                    if let Some(axis) = Self::axis(axis) {
+                        events.push(GamepadEvent::AxisChanged(player, axis, value));
+                    }
+                }
+                _ => (),
+            }
+        }
+        events
+    }
+}
+
+pub struct GamepadSystem;
+
+impl GamepadSystem {
+    /// gilrs when the `gilrs` feature is enabled and controllers are supported on this
+    /// platform, otherwise `None` and no gamepad input is reported.
+    pub fn default_backend() -> Option<Box<dyn GamepadBackend>> {
+        #[cfg(feature = "gilrs")]
+        if let Ok(backend) = GilrsGamepadBackend::new() {
+            return Some(Box::new(backend));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{EffectEvent, EffectEventSystem};
+
+    const PAD: GamepadID = GamepadID(0);
+
+    // Polls the backend into the context the way the main loop does
+    fn poll(backend: &mut MockGamepadBackend, context: &mut EffectEvent) {
+        for event in backend.poll() {
+            EffectEventSystem::gamepad_event_update(context, &event);
+        }
+    }
+
+    fn connected() -> (MockGamepadBackend, EffectEvent) {
+        let mut backend = MockGamepadBackend::new();
+        let mut context = EffectEvent::new();
+        backend.connect(PAD, "Pad");
+        poll(&mut backend, &mut context);
+        (backend, context)
+    }
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-5,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn stick_inside_dead_zone_reads_zero() {
+        let (mut backend, mut context) = connected();
+        backend.set_axis(PAD, GamepadAxis::LeftStickX, 0.1);
+        backend.set_axis(PAD, GamepadAxis::LeftStickY, -0.1);
+        poll(&mut backend, &mut context);
+        assert_eq!(context.gamepad_stick(PAD, GamepadStick::Left), (0.0, 0.0));
+        assert_eq!(context.gamepad_axis(PAD, GamepadAxis::LeftStickX), 0.0);
+    }
+
+    #[test]
+    fn stick_outside_dead_zone_is_rescaled_from_zero() {
+        let (mut backend, mut context) = connected();
+        // Half way between the default 0.15 dead zone and full tilt
+        backend.set_axis(PAD, GamepadAxis::RightStickX, 0.575);
+        poll(&mut backend, &mut context);
+        assert_near(context.gamepad_axis(PAD, GamepadAxis::RightStickX), 0.5);
+        backend.set_axis(PAD, GamepadAxis::RightStickX, 1.0);
+        poll(&mut backend, &mut context);
+        assert_near(context.gamepad_axis(PAD, GamepadAxis::RightStickX), 1.0);
+    }
+
+    #[test]
+    fn stick_dead_zone_is_radial() {
+        let (mut backend, mut context) = connected();
+        // Each axis is inside the dead zone but the diagonal is not
+        backend.set_axis(PAD, GamepadAxis::LeftStickX, 0.12);
+        backend.set_axis(PAD, GamepadAxis::LeftStickY, 0.12);
+        poll(&mut backend, &mut context);
+        let (x, y) = context.gamepad_stick(PAD, GamepadStick::Left);
+        assert!(x > 0.0 && y > 0.0);
+        assert_near(x, y);
+    }
+
+    #[test]
+    fn trigger_dead_zone() {
+        let (mut backend, mut context) = connected();
+        backend.set_axis(PAD, GamepadAxis::LeftTrigger, 0.04);
+        poll(&mut backend, &mut context);
+        assert_eq!(context.gamepad_axis(PAD, GamepadAxis::LeftTrigger), 0.0);
+        backend.set_axis(PAD, GamepadAxis::LeftTrigger, 0.525);
+        poll(&mut backend, &mut context);
+        assert_near(context.gamepad_axis(PAD, GamepadAxis::LeftTrigger), 0.5);
+    }
+
+    #[test]
+    fn custom_dead_zones() {
+        let (mut backend, mut context) = connected();
+        EffectEventSystem::set_dead_zones(
+            &mut context,
+            GamepadDeadZones {
+                stick: 0.5,
+                trigger: 0.0,
+            },
+        );
+        backend.set_axis(PAD, GamepadAxis::LeftStickX, 0.4);
+        backend.set_axis(PAD, GamepadAxis::RightTrigger, 0.04);
+        poll(&mut backend, &mut context);
+        assert_eq!(context.gamepad_axis(PAD, GamepadAxis::LeftStickX), 0.0);
+        assert_near(context.gamepad_axis(PAD, GamepadAxis::RightTrigger), 0.04);
+    }
+
+    #[test]
+    fn hot_plug_is_reported_for_one_frame() {
+        let mut backend = MockGamepadBackend::new();
+        let mut context = EffectEvent::new();
+        assert!(context.gamepads().is_empty());
+
+        backend.connect(GamepadID(0), "First");
+        backend.connect(GamepadID(1), "Second");
+        poll(&mut backend, &mut context);
+        assert_eq!(context.gamepads(), vec![GamepadID(0), GamepadID(1)]);
+        assert!(context.is_gamepad_just_connected(GamepadID(1)));
+        assert_eq!(context.gamepad_name(GamepadID(1)), Some("Second"));
+
+        EffectEventSystem::clear_released(&mut context);
+        assert!(!context.is_gamepad_just_connected(GamepadID(1)));
+        assert!(context.is_gamepad_connected(GamepadID(1)));
+
+        backend.disconnect(GamepadID(0));
+        poll(&mut backend, &mut context);
+        assert!(context.is_gamepad_just_disconnected(GamepadID(0)));
+        assert_eq!(context.gamepads(), vec![GamepadID(1)]);
+
+        EffectEventSystem::clear_released(&mut context);
+        assert!(!context.is_gamepad_just_disconnected(GamepadID(0)));
+    }
+
+    #[test]
+    fn unplugging_drops_held_input() {
+        let (mut backend, mut context) = connected();
+        backend.press(PAD, GamepadButton::South);
+        backend.set_axis(PAD, GamepadAxis::LeftStickX, 1.0);
+        poll(&mut backend, &mut context);
+        assert!(context.is_gamepad_button_pressed(PAD, GamepadButton::South));
+
+        backend.disconnect(PAD);
+        poll(&mut backend, &mut context);
+        assert!(!context.is_gamepad_button_pressed(PAD, GamepadButton::South));
+        assert_eq!(context.gamepad_axis(PAD, GamepadAxis::LeftStickX), 0.0);
+
+        // Reconnecting starts from a clean state
+        backend.connect(PAD, "Pad");
+        poll(&mut backend, &mut context);
+        EffectEventSystem::clear_released(&mut context);
+        assert!(context.is_gamepad_connected(PAD));
+        assert!(!context.is_gamepad_button_pressed(PAD, GamepadButton::South));
+        assert_eq!(context.gamepad_axis(PAD, GamepadAxis::LeftStickX), 0.0);
+    }
+
+    #[test]
+    fn input_from_unknown_gamepads_is_ignored() {
+        let (mut backend, mut context) = connected();
+        backend.press(GamepadID(3), GamepadButton::Start);
+        poll(&mut backend, &mut context);
+        assert!(!context.is_gamepad_connected(GamepadID(3)));
+        assert!(context.just_pressed_gamepad_buttons().is_empty());
+    }
+
+    #[test]
+    fn clones_share_the_queue() {
+        let mut backend = MockGamepadBackend::new();
+        let injector = backend.clone();
+        injector.connect(PAD, "Pad");
+        assert_eq!(
+            backend.poll(),
+            vec![GamepadEvent::Connected(PAD, String::from("Pad"))]
+        );
+        assert!(backend.poll().is_empty());
+    }
+}
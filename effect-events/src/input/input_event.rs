@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{DeviceEvent, ElementState, Ime, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{ModifiersState, PhysicalKey},
+};
+
+use super::gamepad::GamepadEvent;
+use super::TouchPoint;
+
+/// The parts of winit's window and device events that `EffectEvent` reads.
+/// Unlike winit's events these can be built by hand, stored and replayed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    CloseRequested,
+    Resized(PhysicalSize<u32>),
+    KeyPressed {
+        key: PhysicalKey,
+        repeat: bool,
+        text: Option<String>,
+    },
+    KeyReleased(PhysicalKey),
+    MousePressed(MouseButton),
+    MouseReleased(MouseButton),
+    CursorMoved(PhysicalPosition<f64>),
+    CursorEntered,
+    CursorLeft,
+    ScrollLines(f32, f32),
+    ScrollPixels(f64, f64),
+    Touch(TouchPoint),
+    Modifiers(ModifiersState),
+    ImePreedit(String, Option<(usize, usize)>),
+    ImeCommit(String),
+    /// IME was enabled or disabled, either way any composition is dropped.
+    ImeToggled,
+    MouseMotion(f64, f64),
+    Gamepad(GamepadEvent),
+}
+
+impl InputEvent {
+    /// `None` for window events that are not input, such as redraw requests.
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        Some(match event {
+            WindowEvent::CloseRequested => InputEvent::CloseRequested,
+            WindowEvent::Resized(size) => InputEvent::Resized(*size),
+            WindowEvent::KeyboardInput { event, .. } => match event.state {
+                ElementState::Pressed => InputEvent::KeyPressed {
+                    key: event.physical_key,
+                    repeat: event.repeat,
+                    text: event.text.as_ref().map(|text| text.to_string()),
+                },
+                ElementState::Released => InputEvent::KeyReleased(event.physical_key),
+            },
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed => InputEvent::MousePressed(*button),
+                ElementState::Released => InputEvent::MouseReleased(*button),
+            },
+            WindowEvent::CursorMoved { position, .. } => InputEvent::CursorMoved(*position),
+            WindowEvent::CursorEntered { .. } => InputEvent::CursorEntered,
+            WindowEvent::CursorLeft { .. } => InputEvent::CursorLeft,
+            WindowEvent::MouseWheel { delta, .. } => match delta {
+                MouseScrollDelta::LineDelta(x, y) => InputEvent::ScrollLines(*x, *y),
+                MouseScrollDelta::PixelDelta(position) => {
+                    InputEvent::ScrollPixels(position.x, position.y)
+                }
+            },
+            WindowEvent::Touch(touch) => InputEvent::Touch(TouchPoint {
+                id: touch.id,
+                position: touch.location,
+                phase: touch.phase,
+                force: touch.force.map(|force| force.normalized()),
+            }),
+            WindowEvent::ModifiersChanged(modifiers) => InputEvent::Modifiers(modifiers.state()),
+            WindowEvent::Ime(ime) => match ime {
+                Ime::Preedit(text, cursor) => InputEvent::ImePreedit(text.clone(), *cursor),
+                Ime::Commit(text) => InputEvent::ImeCommit(text.clone()),
+                Ime::Enabled | Ime::Disabled => InputEvent::ImeToggled,
+            },
+            _ => return None,
+        })
+    }
+
+    pub fn from_device_event(event: &DeviceEvent) -> Option<Self> {
+        match event {
+            DeviceEvent::MouseMotion { delta } => Some(InputEvent::MouseMotion(delta.0, delta.1)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,787 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::hash::Hash;
+use std::path::Path;
+
+use anyhow::Result;
+use effect_util::effect_error::EffectError;
+use serde::{Deserialize, Serialize};
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+use super::gamepad::{GamepadAxis, GamepadButton, GamepadID};
+use super::EffectEvent;
+
+/// A physical input that can drive an action or one side of an axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadID, GamepadButton),
+}
+
+impl Binding {
+    /// A press and release within the same frame still counts as pressed for that frame.
+    pub fn is_pressed(&self, ctx: &EffectEvent) -> bool {
+        match self {
+            Binding::Key(key) => ctx.is_key_pressed(*key) || ctx.is_key_just_pressed(*key),
+            Binding::Mouse(button) => {
+                ctx.is_mouse_pressed(*button) || ctx.is_mouse_just_pressed(*button)
+            }
+            Binding::Gamepad(id, button) => {
+                ctx.is_gamepad_button_pressed(*id, *button)
+                    || ctx.is_gamepad_button_just_pressed(*id, *button)
+            }
+        }
+    }
+}
+
+/// Input read as a value between -1.0 and 1.0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AxisBinding {
+    /// A pair of bindings, such as two keys.
+    Digital {
+        negative: Binding,
+        positive: Binding,
+    },
+    /// An analog stick or trigger, with dead zones applied.
+    Gamepad(GamepadID, GamepadAxis),
+}
+
+impl AxisBinding {
+    pub fn value(&self, ctx: &EffectEvent) -> f32 {
+        match self {
+            AxisBinding::Digital { negative, positive } => {
+                match (negative.is_pressed(ctx), positive.is_pressed(ctx)) {
+                    (true, false) => -1.0,
+                    (false, true) => 1.0,
+                    _ => 0.0,
+                }
+            }
+            AxisBinding::Gamepad(id, axis) => ctx.gamepad_axis(*id, *axis),
+        }
+    }
+
+    pub fn uses(&self, binding: Binding) -> bool {
+        match self {
+            AxisBinding::Digital { negative, positive } => {
+                *negative == binding || *positive == binding
+            }
+            AxisBinding::Gamepad(..) => false,
+        }
+    }
+}
+
+/// A binding captured while listening, see `InputMap::listen`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rebind<A> {
+    pub action: A,
+    pub binding: Binding,
+    /// The binding it took the place of, if any.
+    pub replaced: Option<Binding>,
+    /// Other actions and axes already using the binding, they are left bound.
+    pub conflicts: Vec<A>,
+}
+
+// Bindings as written to disk, keyed by the serde name of each action
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct InputBindings {
+    actions: BTreeMap<String, Vec<Binding>>,
+    axes: BTreeMap<String, Vec<AxisBinding>>,
+}
+
+/// Named actions and axes declared by the game, each with any number of bindings.
+/// `A` is usually a small enum, the same type can name both actions and axes.
+/// Query it after `InputMapSystem::update` has run for the frame.
+pub struct InputMap<A> {
+    actions: HashMap<A, Vec<Binding>>,
+    axes: HashMap<A, Vec<AxisBinding>>,
+    pressed: HashSet<A>,
+    previous: HashSet<A>,
+    axis_values: HashMap<A, f32>,
+    default_actions: HashMap<A, Vec<Binding>>,
+    default_axes: HashMap<A, Vec<AxisBinding>>,
+    listening: Option<(A, Option<Binding>)>,
+    rebind: Option<Rebind<A>>,
+}
+
+impl<A: Copy + Eq + Hash> InputMap<A> {
+    pub fn new() -> Self {
+        let actions = HashMap::new();
+        let axes = HashMap::new();
+        let pressed = HashSet::new();
+        let previous = HashSet::new();
+        let axis_values = HashMap::new();
+        let default_actions = HashMap::new();
+        let default_axes = HashMap::new();
+        let listening = None;
+        let rebind = None;
+        Self {
+            actions,
+            axes,
+            pressed,
+            previous,
+            axis_values,
+            default_actions,
+            default_axes,
+            listening,
+            rebind,
+        }
+    }
+
+    /// Adds a binding to the action, existing bindings are kept.
+    pub fn bind(&mut self, action: A, binding: Binding) {
+        let bindings = self.actions.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Adds a pair of bindings to the axis, existing bindings are kept.
+    pub fn bind_axis(&mut self, axis: A, negative: Binding, positive: Binding) {
+        self.bind_axis_input(axis, AxisBinding::Digital { negative, positive });
+    }
+
+    /// Adds a gamepad stick axis or trigger to the axis, existing bindings are kept.
+    pub fn bind_gamepad_axis(&mut self, axis: A, id: GamepadID, gamepad_axis: GamepadAxis) {
+        self.bind_axis_input(axis, AxisBinding::Gamepad(id, gamepad_axis));
+    }
+
+    pub fn bind_axis_input(&mut self, axis: A, binding: AxisBinding) {
+        let bindings = self.axes.entry(axis).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Removes every binding from the action or axis.
+    pub fn unbind(&mut self, action: A) {
+        self.actions.remove(&action);
+        self.axes.remove(&action);
+        self.pressed.remove(&action);
+        self.previous.remove(&action);
+        self.axis_values.remove(&action);
+    }
+
+    /// Swaps one binding of the action for another, keeping its position.
+    /// When `old` is not bound to the action, `new` is added instead.
+    pub fn replace(&mut self, action: A, old: Binding, new: Binding) {
+        let bindings = self.actions.entry(action).or_default();
+        let already_bound = old != new && bindings.contains(&new);
+        match bindings.iter().position(|binding| *binding == old) {
+            Some(index) if already_bound => {
+                bindings.remove(index);
+            }
+            Some(index) => bindings[index] = new,
+            None if bindings.contains(&new) => (),
+            None => bindings.push(new),
+        }
+    }
+
+    /// Removes a single binding from the action.
+    pub fn unbind_binding(&mut self, action: A, binding: Binding) {
+        if let Some(bindings) = self.actions.get_mut(&action) {
+            bindings.retain(|bound| *bound != binding);
+        }
+    }
+
+    /// Actions and axes other than `action` that already use `binding`.
+    pub fn conflicts(&self, action: A, binding: Binding) -> Vec<A> {
+        let mut conflicts: Vec<A> = self
+            .actions
+            .iter()
+            .filter(|(other, bindings)| **other != action && bindings.contains(&binding))
+            .map(|(other, _)| *other)
+            .collect();
+        for (other, bindings) in self.axes.iter() {
+            if *other != action
+                && !conflicts.contains(other)
+                && bindings.iter().any(|axis| axis.uses(binding))
+            {
+                conflicts.push(*other);
+            }
+        }
+        conflicts
+    }
+
+    /// Remembers the current bindings as the defaults, call once the game's own
+    /// bindings are set up and before loading the player's.
+    pub fn store_defaults(&mut self) {
+        self.default_actions = self.actions.clone();
+        self.default_axes = self.axes.clone();
+    }
+
+    pub fn reset_to_defaults(&mut self) {
+        self.actions = self.default_actions.clone();
+        self.axes = self.default_axes.clone();
+    }
+
+    pub fn reset_action(&mut self, action: A) {
+        match self.default_actions.get(&action) {
+            Some(bindings) => self.actions.insert(action, bindings.clone()),
+            None => self.actions.remove(&action),
+        };
+        match self.default_axes.get(&action) {
+            Some(bindings) => self.axes.insert(action, bindings.clone()),
+            None => self.axes.remove(&action),
+        };
+    }
+
+    /// Binds the next key, mouse button or gamepad button pressed to the action,
+    /// replacing `replacing` if given. Nothing reports as just pressed or released
+    /// while listening, so the press does not trigger anything.
+    /// Collect the result with `InputMapSystem::take_rebind`.
+    pub fn listen(&mut self, action: A, replacing: Option<Binding>) {
+        self.listening = Some((action, replacing));
+        self.rebind = None;
+    }
+
+    pub fn cancel_listen(&mut self) {
+        self.listening = None;
+    }
+
+    pub fn listening(&self) -> Option<A> {
+        self.listening.map(|(action, _)| action)
+    }
+
+    pub fn bindings(&self, action: A) -> &[Binding] {
+        self.actions.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn axis_bindings(&self, axis: A) -> &[AxisBinding] {
+        self.axes.get(&axis).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// True while any binding of the action is held.
+    pub fn action_pressed(&self, action: A) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    /// True on the first frame the action is held.
+    pub fn action_just_pressed(&self, action: A) -> bool {
+        self.pressed.contains(&action) && !self.previous.contains(&action)
+    }
+
+    /// True on the first frame the action is no longer held.
+    pub fn action_just_released(&self, action: A) -> bool {
+        !self.pressed.contains(&action) && self.previous.contains(&action)
+    }
+
+    /// Sum of the axis bindings, clamped to -1.0..=1.0. Zero for unbound axes.
+    pub fn axis_value(&self, axis: A) -> f32 {
+        self.axis_values.get(&axis).copied().unwrap_or(0.0)
+    }
+}
+
+impl<A: Copy + Eq + Hash> Default for InputMap<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct InputMapSystem;
+
+impl InputMapSystem {
+    /// Reads the bound inputs from `ctx`, call once per frame before querying the map.
+    pub fn update<A: Copy + Eq + Hash>(map: &mut InputMap<A>, ctx: &EffectEvent) {
+        let listening = map.listening.is_some();
+        if let Some((action, replacing)) = map.listening {
+            if let Some(binding) = Self::next_binding(ctx) {
+                match replacing {
+                    Some(old) => map.replace(action, old, binding),
+                    None => map.bind(action, binding),
+                }
+                let conflicts = map.conflicts(action, binding);
+                map.rebind = Some(Rebind {
+                    action,
+                    binding,
+                    replaced: replacing,
+                    conflicts,
+                });
+                map.listening = None;
+            }
+        }
+        std::mem::swap(&mut map.previous, &mut map.pressed);
+        map.pressed.clear();
+        for (action, bindings) in map.actions.iter() {
+            if bindings.iter().any(|binding| binding.is_pressed(ctx)) {
+                map.pressed.insert(*action);
+            }
+        }
+        map.axis_values.clear();
+        for (axis, bindings) in map.axes.iter() {
+            let value: f32 = bindings.iter().map(|binding| binding.value(ctx)).sum();
+            map.axis_values.insert(*axis, value.clamp(-1.0, 1.0));
+        }
+        if listening {
+            map.previous = map.pressed.clone();
+        }
+    }
+
+    /// The input that went down this frame, keys first, then mouse and gamepad buttons.
+    pub fn next_binding(ctx: &EffectEvent) -> Option<Binding> {
+        if let Some(key) = ctx.just_pressed_keys().first() {
+            return Some(Binding::Key(*key));
+        }
+        if let Some(button) = ctx.just_pressed_mouse_buttons().first() {
+            return Some(Binding::Mouse(*button));
+        }
+        ctx.just_pressed_gamepad_buttons()
+            .first()
+            .map(|(id, button)| Binding::Gamepad(*id, *button))
+    }
+
+    /// The binding captured since `InputMap::listen`, once.
+    pub fn take_rebind<A>(map: &mut InputMap<A>) -> Option<Rebind<A>> {
+        map.rebind.take()
+    }
+
+    /// Writes every action's and axis' bindings as TOML, keyed by each action's serde name,
+    /// see `action_name`.
+    pub fn save<A: Copy + Eq + Hash + Serialize>(
+        map: &InputMap<A>,
+        path: impl AsRef<Path>,
+    ) -> Result<()> {
+        Self::save_with(map, path, Self::action_name)
+    }
+
+    /// Replaces the bindings of every action and axis named in the file.
+    /// Actions missing from the file keep their bindings and unknown names are skipped,
+    /// so saved files survive actions being added or removed.
+    pub fn load<A: Copy + Eq + Hash + Serialize>(
+        map: &mut InputMap<A>,
+        path: impl AsRef<Path>,
+    ) -> Result<()> {
+        Self::load_with(map, path, Self::action_name)
+    }
+
+    /// The name an action is saved under, its serde representation. Enum variants without
+    /// data use the variant name, `#[serde(rename)]` keeps old files loading after a rename.
+    pub fn action_name<A: Serialize>(action: &A) -> Result<String> {
+        match toml::Value::try_from(action)? {
+            toml::Value::String(name) => Ok(name),
+            _ => Err(EffectError::new(
+                "Only actions that serialise as strings can be saved, such as enum variants without data",
+            )
+            .into()),
+        }
+    }
+
+    pub(crate) fn save_with<A: Copy + Eq + Hash>(
+        map: &InputMap<A>,
+        path: impl AsRef<Path>,
+        name: impl Fn(&A) -> Result<String>,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let bindings = InputBindings {
+            actions: map
+                .actions
+                .iter()
+                .map(|(action, bindings)| Ok((name(action)?, bindings.clone())))
+                .collect::<Result<_>>()?,
+            axes: map
+                .axes
+                .iter()
+                .map(|(axis, bindings)| Ok((name(axis)?, bindings.clone())))
+                .collect::<Result<_>>()?,
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, toml::to_string_pretty(&bindings)?)?;
+        Ok(())
+    }
+
+    pub(crate) fn load_with<A: Copy + Eq + Hash>(
+        map: &mut InputMap<A>,
+        path: impl AsRef<Path>,
+        name: impl Fn(&A) -> Result<String>,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let mut bindings: InputBindings = toml::from_str(&text).map_err(|e| {
+            EffectError::new(&format!("Invalid bindings file {}: {e}", path.display()))
+        })?;
+        let actions: HashSet<A> = map
+            .actions
+            .keys()
+            .chain(map.default_actions.keys())
+            .copied()
+            .collect();
+        for action in actions {
+            if let Some(loaded) = bindings.actions.remove(&name(&action)?) {
+                map.actions.insert(action, loaded);
+            }
+        }
+        let axes: HashSet<A> = map
+            .axes
+            .keys()
+            .chain(map.default_axes.keys())
+            .copied()
+            .collect();
+        for axis in axes {
+            if let Some(loaded) = bindings.axes.remove(&name(&axis)?) {
+                map.axes.insert(axis, loaded);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::keyboard::PhysicalKey;
+
+    use super::*;
+    use crate::input::gamepad::GamepadEvent;
+    use crate::input::input_event::InputEvent;
+    use crate::input::EffectEventSystem;
+
+    const PAD: GamepadID = GamepadID(0);
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+    enum Action {
+        Jump,
+        #[serde(rename = "Shoot")]
+        Fire,
+        MoveX,
+    }
+
+    // Unique per test and process so tests can run in parallel
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "effect-bindings-{}-{name}.toml",
+            std::process::id()
+        ))
+    }
+
+    fn press(ctx: &mut EffectEvent, key: KeyCode) {
+        let event = InputEvent::KeyPressed {
+            key: PhysicalKey::Code(key),
+            repeat: false,
+            text: None,
+        };
+        EffectEventSystem::input_event_update(ctx, &event);
+    }
+
+    fn release(ctx: &mut EffectEvent, key: KeyCode) {
+        let event = InputEvent::KeyReleased(PhysicalKey::Code(key));
+        EffectEventSystem::input_event_update(ctx, &event);
+    }
+
+    fn gamepad(ctx: &mut EffectEvent, event: GamepadEvent) {
+        EffectEventSystem::gamepad_event_update(ctx, &event);
+    }
+
+    // Reads the frame's input into the map, before the hooks query it
+    fn frame(map: &mut InputMap<Action>, ctx: &mut EffectEvent) {
+        InputMapSystem::update(map, ctx);
+    }
+
+    fn end_frame(ctx: &mut EffectEvent) {
+        EffectEventSystem::clear_released(ctx);
+    }
+
+    #[test]
+    fn any_binding_presses_the_action() {
+        let mut map = InputMap::new();
+        map.bind(Action::Jump, Binding::Key(KeyCode::Space));
+        map.bind(Action::Jump, Binding::Mouse(MouseButton::Left));
+        map.bind(Action::Jump, Binding::Gamepad(PAD, GamepadButton::South));
+        let mut ctx = EffectEvent::new();
+        gamepad(&mut ctx, GamepadEvent::Connected(PAD, String::from("Pad")));
+
+        press(&mut ctx, KeyCode::Space);
+        frame(&mut map, &mut ctx);
+        assert!(map.action_pressed(Action::Jump));
+        end_frame(&mut ctx);
+
+        // Still held by the gamepad after the key goes up
+        gamepad(
+            &mut ctx,
+            GamepadEvent::ButtonPressed(PAD, GamepadButton::South),
+        );
+        release(&mut ctx, KeyCode::Space);
+        frame(&mut map, &mut ctx);
+        assert!(map.action_pressed(Action::Jump));
+        assert!(!map.action_just_pressed(Action::Jump));
+        assert!(!map.action_just_released(Action::Jump));
+        assert!(!map.action_pressed(Action::Fire));
+    }
+
+    #[test]
+    fn just_pressed_and_released_last_one_frame() {
+        let mut map = InputMap::new();
+        map.bind(Action::Fire, Binding::Key(KeyCode::KeyF));
+        let mut ctx = EffectEvent::new();
+
+        press(&mut ctx, KeyCode::KeyF);
+        frame(&mut map, &mut ctx);
+        assert!(map.action_just_pressed(Action::Fire));
+        end_frame(&mut ctx);
+
+        frame(&mut map, &mut ctx);
+        assert!(map.action_pressed(Action::Fire));
+        assert!(!map.action_just_pressed(Action::Fire));
+        end_frame(&mut ctx);
+
+        release(&mut ctx, KeyCode::KeyF);
+        frame(&mut map, &mut ctx);
+        assert!(map.action_just_released(Action::Fire));
+        end_frame(&mut ctx);
+
+        frame(&mut map, &mut ctx);
+        assert!(!map.action_just_released(Action::Fire));
+    }
+
+    #[test]
+    fn tap_within_a_frame_still_presses() {
+        let mut map = InputMap::new();
+        map.bind(Action::Fire, Binding::Key(KeyCode::KeyF));
+        let mut ctx = EffectEvent::new();
+        press(&mut ctx, KeyCode::KeyF);
+        release(&mut ctx, KeyCode::KeyF);
+        frame(&mut map, &mut ctx);
+        assert!(map.action_just_pressed(Action::Fire));
+    }
+
+    #[test]
+    fn digital_axes_cancel_out() {
+        let mut map = InputMap::new();
+        map.bind_axis(
+            Action::MoveX,
+            Binding::Key(KeyCode::KeyA),
+            Binding::Key(KeyCode::KeyD),
+        );
+        let mut ctx = EffectEvent::new();
+        frame(&mut map, &mut ctx);
+        end_frame(&mut ctx);
+        assert_eq!(map.axis_value(Action::MoveX), 0.0);
+
+        press(&mut ctx, KeyCode::KeyD);
+        frame(&mut map, &mut ctx);
+        end_frame(&mut ctx);
+        assert_eq!(map.axis_value(Action::MoveX), 1.0);
+
+        press(&mut ctx, KeyCode::KeyA);
+        frame(&mut map, &mut ctx);
+        end_frame(&mut ctx);
+        assert_eq!(map.axis_value(Action::MoveX), 0.0);
+
+        release(&mut ctx, KeyCode::KeyD);
+        frame(&mut map, &mut ctx);
+        end_frame(&mut ctx);
+        assert_eq!(map.axis_value(Action::MoveX), -1.0);
+    }
+
+    #[test]
+    fn keyboard_and_gamepad_axes_add_up_and_clamp() {
+        let mut map = InputMap::new();
+        map.bind_axis(
+            Action::MoveX,
+            Binding::Key(KeyCode::KeyA),
+            Binding::Key(KeyCode::KeyD),
+        );
+        map.bind_gamepad_axis(Action::MoveX, PAD, GamepadAxis::LeftStickX);
+        let mut ctx = EffectEvent::new();
+        gamepad(&mut ctx, GamepadEvent::Connected(PAD, String::from("Pad")));
+        gamepad(
+            &mut ctx,
+            GamepadEvent::AxisChanged(PAD, GamepadAxis::LeftStickX, 1.0),
+        );
+        frame(&mut map, &mut ctx);
+        end_frame(&mut ctx);
+        assert_eq!(map.axis_value(Action::MoveX), 1.0);
+
+        // Pushing the other way on the keyboard cancels the stick
+        press(&mut ctx, KeyCode::KeyA);
+        frame(&mut map, &mut ctx);
+        end_frame(&mut ctx);
+        assert_eq!(map.axis_value(Action::MoveX), 0.0);
+
+        // Both the same way is still at most 1.0
+        release(&mut ctx, KeyCode::KeyA);
+        press(&mut ctx, KeyCode::KeyD);
+        frame(&mut map, &mut ctx);
+        end_frame(&mut ctx);
+        assert_eq!(map.axis_value(Action::MoveX), 1.0);
+    }
+
+    #[test]
+    fn unbound_actions_read_as_idle() {
+        let mut map = InputMap::new();
+        map.bind(Action::Jump, Binding::Key(KeyCode::Space));
+        let mut ctx = EffectEvent::new();
+        press(&mut ctx, KeyCode::Space);
+        frame(&mut map, &mut ctx);
+        map.unbind(Action::Jump);
+        assert!(!map.action_pressed(Action::Jump));
+        assert_eq!(map.axis_value(Action::MoveX), 0.0);
+        assert!(map.bindings(Action::Jump).is_empty());
+    }
+
+    #[test]
+    fn listening_binds_the_next_press_without_triggering_it() {
+        let mut map = InputMap::new();
+        map.bind(Action::Jump, Binding::Key(KeyCode::Space));
+        map.bind(Action::Fire, Binding::Key(KeyCode::KeyF));
+        map.listen(Action::Jump, Some(Binding::Key(KeyCode::Space)));
+        let mut ctx = EffectEvent::new();
+        frame(&mut map, &mut ctx);
+        end_frame(&mut ctx);
+        assert_eq!(map.listening(), Some(Action::Jump));
+        assert_eq!(InputMapSystem::take_rebind(&mut map), None);
+
+        press(&mut ctx, KeyCode::KeyF);
+        frame(&mut map, &mut ctx);
+        assert!(!map.action_just_pressed(Action::Fire));
+        assert!(!map.action_just_pressed(Action::Jump));
+        assert_eq!(map.listening(), None);
+        assert_eq!(map.bindings(Action::Jump), &[Binding::Key(KeyCode::KeyF)]);
+        let rebind = InputMapSystem::take_rebind(&mut map).unwrap();
+        assert_eq!(
+            rebind,
+            Rebind {
+                action: Action::Jump,
+                binding: Binding::Key(KeyCode::KeyF),
+                replaced: Some(Binding::Key(KeyCode::Space)),
+                conflicts: vec![Action::Fire],
+            }
+        );
+        assert_eq!(InputMapSystem::take_rebind(&mut map), None);
+    }
+
+    #[test]
+    fn listening_picks_up_gamepad_buttons_and_can_be_cancelled() {
+        let mut map = InputMap::new();
+        let mut ctx = EffectEvent::new();
+        gamepad(&mut ctx, GamepadEvent::Connected(PAD, String::from("Pad")));
+        map.listen(Action::Jump, None);
+        map.cancel_listen();
+        press(&mut ctx, KeyCode::Space);
+        frame(&mut map, &mut ctx);
+        end_frame(&mut ctx);
+        assert!(map.bindings(Action::Jump).is_empty());
+
+        map.listen(Action::Jump, None);
+        gamepad(
+            &mut ctx,
+            GamepadEvent::ButtonPressed(PAD, GamepadButton::South),
+        );
+        frame(&mut map, &mut ctx);
+        assert_eq!(
+            map.bindings(Action::Jump),
+            &[Binding::Gamepad(PAD, GamepadButton::South)]
+        );
+    }
+
+    #[test]
+    fn conflicts_include_axes() {
+        let mut map = InputMap::new();
+        map.bind_axis(
+            Action::MoveX,
+            Binding::Key(KeyCode::KeyA),
+            Binding::Key(KeyCode::KeyD),
+        );
+        map.bind(Action::Fire, Binding::Key(KeyCode::KeyD));
+        assert_eq!(
+            map.conflicts(Action::Jump, Binding::Key(KeyCode::KeyD))
+                .len(),
+            2
+        );
+        assert_eq!(
+            map.conflicts(Action::Fire, Binding::Key(KeyCode::KeyD)),
+            vec![Action::MoveX]
+        );
+    }
+
+    #[test]
+    fn reset_restores_the_stored_defaults() {
+        let mut map = InputMap::new();
+        map.bind(Action::Jump, Binding::Key(KeyCode::Space));
+        map.store_defaults();
+        map.replace(
+            Action::Jump,
+            Binding::Key(KeyCode::Space),
+            Binding::Key(KeyCode::KeyW),
+        );
+        map.bind(Action::Fire, Binding::Key(KeyCode::KeyF));
+        map.reset_action(Action::Jump);
+        assert_eq!(map.bindings(Action::Jump), &[Binding::Key(KeyCode::Space)]);
+        assert_eq!(map.bindings(Action::Fire), &[Binding::Key(KeyCode::KeyF)]);
+        map.reset_to_defaults();
+        assert!(map.bindings(Action::Fire).is_empty());
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let path = temp_path("round-trip");
+        let mut saved = InputMap::new();
+        saved.bind(Action::Jump, Binding::Key(KeyCode::Space));
+        saved.bind(Action::Jump, Binding::Gamepad(PAD, GamepadButton::South));
+        saved.bind(Action::Fire, Binding::Mouse(MouseButton::Left));
+        saved.bind_gamepad_axis(Action::MoveX, PAD, GamepadAxis::LeftStickX);
+        InputMapSystem::save(&saved, &path).unwrap();
+
+        let mut loaded = InputMap::new();
+        loaded.bind(Action::Jump, Binding::Key(KeyCode::KeyW));
+        loaded.bind(Action::Fire, Binding::Key(KeyCode::KeyF));
+        loaded.bind_axis(
+            Action::MoveX,
+            Binding::Key(KeyCode::KeyA),
+            Binding::Key(KeyCode::KeyD),
+        );
+        InputMapSystem::load(&mut loaded, &path).unwrap();
+        fs::remove_file(&path).unwrap();
+        for action in [Action::Jump, Action::Fire] {
+            assert_eq!(loaded.bindings(action), saved.bindings(action));
+        }
+        assert_eq!(
+            loaded.axis_bindings(Action::MoveX),
+            saved.axis_bindings(Action::MoveX)
+        );
+    }
+
+    #[test]
+    fn files_are_keyed_by_serde_name() {
+        let path = temp_path("names");
+        let mut map = InputMap::new();
+        map.bind(Action::Fire, Binding::Key(KeyCode::KeyF));
+        InputMapSystem::save(&map, &path).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(text.contains("Shoot"), "{text}");
+        assert!(!text.contains("Fire"), "{text}");
+    }
+
+    #[test]
+    fn load_keeps_actions_missing_from_the_file_and_skips_unknown_names() {
+        let path = temp_path("partial");
+        fs::write(
+            &path,
+            "[actions]\nJump = [{ Key = \"KeyW\" }]\nDance = [{ Key = \"KeyX\" }]\n",
+        )
+        .unwrap();
+        let mut map = InputMap::new();
+        map.bind(Action::Jump, Binding::Key(KeyCode::Space));
+        map.bind(Action::Fire, Binding::Key(KeyCode::KeyF));
+        InputMapSystem::load(&mut map, &path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(map.bindings(Action::Jump), &[Binding::Key(KeyCode::KeyW)]);
+        assert_eq!(map.bindings(Action::Fire), &[Binding::Key(KeyCode::KeyF)]);
+    }
+
+    #[test]
+    fn invalid_files_and_unnameable_actions_are_errors() {
+        let path = temp_path("invalid");
+        fs::write(&path, "actions = 3\n").unwrap();
+        let mut map: InputMap<Action> = InputMap::new();
+        let error = InputMapSystem::load(&mut map, &path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(error.to_string().contains("Invalid bindings file"));
+
+        let mut numbered = InputMap::new();
+        numbered.bind(7u32, Binding::Key(KeyCode::Space));
+        assert!(InputMapSystem::save(&numbered, temp_path("numbered")).is_err());
+    }
+}
@@ -1,43 +1,105 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 pub mod camera2d;
+pub mod camera_controller;
+pub mod gamepad;
+pub mod input_event;
+pub mod input_map;
+pub mod pixel_camera;
+pub mod record;
+use gamepad::{
+    GamepadAxis, GamepadButton, GamepadDeadZones, GamepadEvent, GamepadID, GamepadState,
+    GamepadStick,
+};
+use input_event::InputEvent;
+use serde::{Deserialize, Serialize};
 use winit::{
-    dpi::PhysicalPosition,
-    event::{DeviceEvent, ElementState, Event, MouseButton, WindowEvent},
-    keyboard::{KeyCode, PhysicalKey},
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{DeviceEvent, MouseButton, TouchPhase, WindowEvent},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
 };
 
-// Should provide all the currently pressed keys
-// and the keys released on the previous frame
+/// A finger on a touch screen. Ended and cancelled touches are reported
+/// for the frame they ended on, then removed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TouchPoint {
+    pub id: u64,
+    pub position: PhysicalPosition<f64>,
+    pub phase: TouchPhase,
+    /// Normalised pressure from 0.0 to 1.0, when the device reports it.
+    pub force: Option<f64>,
+}
+
+// Should provide all the currently pressed keys,
+// the keys pressed this frame and the keys released on the previous frame
 pub struct EffectEvent {
     keys_pressed: HashSet<PhysicalKey>,
+    keys_just_pressed: HashSet<PhysicalKey>,
     keys_released: HashSet<PhysicalKey>,
     mouse_pressed: HashSet<MouseButton>,
+    mouse_just_pressed: HashSet<MouseButton>,
     mouse_released: HashSet<MouseButton>,
     mouse_within_window: bool,
     mouse_position: PhysicalPosition<f64>,
     mouse_travel: (f64, f64),
     close_requested: bool,
+    window_size: PhysicalSize<u32>,
+    scroll_lines: (f32, f32),
+    scroll_pixels: (f64, f64),
+    touches: HashMap<u64, TouchPoint>,
+    modifiers: ModifiersState,
+    text: String,
+    ime_preedit: Option<(String, Option<(usize, usize)>)>,
+    gamepads: HashMap<GamepadID, GamepadState>,
+    gamepads_connected: HashSet<GamepadID>,
+    gamepads_disconnected: HashSet<GamepadID>,
+    dead_zones: GamepadDeadZones,
 }
 
 impl EffectEvent {
     pub fn new() -> Self {
         let keys_pressed = HashSet::new();
+        let keys_just_pressed = HashSet::new();
         let keys_released = HashSet::new();
         let mouse_pressed = HashSet::new();
+        let mouse_just_pressed = HashSet::new();
         let mouse_released = HashSet::new();
         let mouse_within_window = false;
         let mouse_position = PhysicalPosition::new(0.0, 0.0);
         let mouse_travel = (0.0, 0.0);
         let close_requested = false;
+        let window_size = PhysicalSize::new(0, 0);
+        let scroll_lines = (0.0, 0.0);
+        let scroll_pixels = (0.0, 0.0);
+        let touches = HashMap::new();
+        let modifiers = ModifiersState::empty();
+        let text = String::new();
+        let ime_preedit = None;
+        let gamepads = HashMap::new();
+        let gamepads_connected = HashSet::new();
+        let gamepads_disconnected = HashSet::new();
+        let dead_zones = GamepadDeadZones::default();
         Self {
             keys_pressed,
+            keys_just_pressed,
             keys_released,
             mouse_pressed,
+            mouse_just_pressed,
             mouse_released,
             mouse_within_window,
             mouse_position,
             mouse_travel,
             close_requested,
+            window_size,
+            scroll_lines,
+            scroll_pixels,
+            touches,
+            modifiers,
+            text,
+            ime_preedit,
+            gamepads,
+            gamepads_connected,
+            gamepads_disconnected,
+            dead_zones,
         }
     }
 
@@ -45,6 +107,22 @@ impl EffectEvent {
         self.keys_pressed.contains(&PhysicalKey::Code(key))
     }
 
+    /// True only on the frame the key went down, OS key repeats are ignored.
+    pub fn is_key_just_pressed(&self, key: KeyCode) -> bool {
+        self.keys_just_pressed.contains(&PhysicalKey::Code(key))
+    }
+
+    /// Keys that went down this frame, in no particular order.
+    pub fn just_pressed_keys(&self) -> Vec<KeyCode> {
+        self.keys_just_pressed
+            .iter()
+            .filter_map(|key| match key {
+                PhysicalKey::Code(key) => Some(*key),
+                PhysicalKey::Unidentified(_) => None,
+            })
+            .collect()
+    }
+
     pub fn is_key_released(&self, key: KeyCode) -> bool {
         self.keys_released.contains(&PhysicalKey::Code(key))
     }
@@ -53,6 +131,16 @@ impl EffectEvent {
         self.mouse_pressed.contains(&button)
     }
 
+    /// True only on the frame the button went down.
+    pub fn is_mouse_just_pressed(&self, button: MouseButton) -> bool {
+        self.mouse_just_pressed.contains(&button)
+    }
+
+    /// Mouse buttons that went down this frame, in no particular order.
+    pub fn just_pressed_mouse_buttons(&self) -> Vec<MouseButton> {
+        self.mouse_just_pressed.iter().copied().collect()
+    }
+
     pub fn is_mouse_released(&self, button: MouseButton) -> bool {
         self.mouse_released.contains(&button)
     }
@@ -61,69 +149,299 @@ impl EffectEvent {
         self.mouse_position
     }
 
+    /// Raw mouse movement this frame, still reported while the cursor is locked.
     pub fn mouse_delta(&self) -> (f64, f64) {
         self.mouse_travel
     }
 
+    /// Whether the window was asked to close this frame.
     pub fn close_requested(&self) -> bool {
         self.close_requested
     }
+
+    /// Inner size of the window in physical pixels, the space `mouse_position` is in.
+    pub fn window_size(&self) -> PhysicalSize<u32> {
+        self.window_size
+    }
+
+    /// Wheel movement this frame in lines, from mice with notched wheels.
+    /// Positive y scrolls up.
+    pub fn scroll_lines(&self) -> (f32, f32) {
+        self.scroll_lines
+    }
+
+    /// Wheel movement this frame in pixels, from touchpads and smooth scrolling mice.
+    pub fn scroll_pixels(&self) -> (f64, f64) {
+        self.scroll_pixels
+    }
+
+    /// Active touches, ordered by ID.
+    pub fn touches(&self) -> Vec<TouchPoint> {
+        let mut touches: Vec<TouchPoint> = self.touches.values().copied().collect();
+        touches.sort_by_key(|touch| touch.id);
+        touches
+    }
+
+    pub fn touch(&self, id: u64) -> Option<TouchPoint> {
+        self.touches.get(&id).copied()
+    }
+
+    pub fn modifiers(&self) -> ModifiersState {
+        self.modifiers
+    }
+
+    /// Characters typed this frame, including key repeats and committed IME text.
+    /// Control characters such as backspace are left out, read those as keys.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Text being composed by the IME, not yet committed. IME input has to be allowed
+    /// first, see `EffectControl::set_ime_allowed`. Persists across frames
+    /// until the composition changes.
+    pub fn ime_preedit(&self) -> Option<&str> {
+        self.ime_preedit.as_ref().map(|(text, _)| text.as_str())
+    }
+
+    /// Byte range of the IME cursor or selection within the preedit text.
+    pub fn ime_cursor(&self) -> Option<(usize, usize)> {
+        self.ime_preedit.as_ref().and_then(|(_, cursor)| *cursor)
+    }
+
+    /// Connected gamepads, ordered by player.
+    pub fn gamepads(&self) -> Vec<GamepadID> {
+        let mut gamepads: Vec<GamepadID> = self.gamepads.keys().copied().collect();
+        gamepads.sort();
+        gamepads
+    }
+
+    pub fn is_gamepad_connected(&self, id: GamepadID) -> bool {
+        self.gamepads.contains_key(&id)
+    }
+
+    pub fn gamepad_name(&self, id: GamepadID) -> Option<&str> {
+        self.gamepads.get(&id).map(|gamepad| gamepad.name.as_str())
+    }
+
+    /// True only on the frame the gamepad was connected.
+    pub fn is_gamepad_just_connected(&self, id: GamepadID) -> bool {
+        self.gamepads_connected.contains(&id)
+    }
+
+    /// True only on the frame the gamepad was disconnected.
+    pub fn is_gamepad_just_disconnected(&self, id: GamepadID) -> bool {
+        self.gamepads_disconnected.contains(&id)
+    }
+
+    pub fn is_gamepad_button_pressed(&self, id: GamepadID, button: GamepadButton) -> bool {
+        self.gamepads
+            .get(&id)
+            .is_some_and(|gamepad| gamepad.buttons_pressed.contains(&button))
+    }
+
+    /// True only on the frame the button went down.
+    pub fn is_gamepad_button_just_pressed(&self, id: GamepadID, button: GamepadButton) -> bool {
+        self.gamepads
+            .get(&id)
+            .is_some_and(|gamepad| gamepad.buttons_just_pressed.contains(&button))
+    }
+
+    /// Gamepad buttons that went down this frame, ordered by player.
+    pub fn just_pressed_gamepad_buttons(&self) -> Vec<(GamepadID, GamepadButton)> {
+        let mut buttons = Vec::new();
+        for id in self.gamepads() {
+            buttons.extend(
+                self.gamepads[&id]
+                    .buttons_just_pressed
+                    .iter()
+                    .map(|button| (id, *button)),
+            );
+        }
+        buttons
+    }
+
+    pub fn is_gamepad_button_released(&self, id: GamepadID, button: GamepadButton) -> bool {
+        self.gamepads
+            .get(&id)
+            .is_some_and(|gamepad| gamepad.buttons_released.contains(&button))
+    }
+
+    /// Axis value with dead zones applied, zero when the gamepad is not connected.
+    pub fn gamepad_axis(&self, id: GamepadID, axis: GamepadAxis) -> f32 {
+        self.gamepads
+            .get(&id)
+            .map_or(0.0, |gamepad| gamepad.axis(axis, self.dead_zones))
+    }
+
+    /// Both axes of a stick with the radial dead zone applied.
+    pub fn gamepad_stick(&self, id: GamepadID, stick: GamepadStick) -> (f32, f32) {
+        self.gamepads
+            .get(&id)
+            .map_or((0.0, 0.0), |gamepad| gamepad.stick(stick, self.dead_zones))
+    }
+
+    pub fn dead_zones(&self) -> GamepadDeadZones {
+        self.dead_zones
+    }
 }
 
 pub struct EffectEventSystem;
 impl EffectEventSystem {
     pub fn device_event_update(context: &mut EffectEvent, event: &DeviceEvent) {
-        match event {
-            DeviceEvent::MouseMotion { delta } => {
-                context.mouse_travel = *delta;
-            }
-            _ => (),
-        };
+        if let Some(input) = InputEvent::from_device_event(event) {
+            Self::input_event_update(context, &input);
+        }
     }
+
     pub fn window_event_update(context: &mut EffectEvent, event: &WindowEvent) {
+        if let Some(input) = InputEvent::from_window_event(event) {
+            Self::input_event_update(context, &input);
+        }
+    }
+
+    /// Applies a single input event, live or replayed.
+    pub fn input_event_update(context: &mut EffectEvent, event: &InputEvent) {
         match event {
-            WindowEvent::CloseRequested => {
+            InputEvent::CloseRequested => {
                 context.close_requested = true;
             }
-            WindowEvent::KeyboardInput { event, .. } => match event.state {
-                ElementState::Pressed => {
-                    context.keys_pressed.insert(event.physical_key);
+            InputEvent::Resized(size) => {
+                context.window_size = *size;
+            }
+            InputEvent::KeyPressed { key, repeat, text } => {
+                // Repeats arrive while the key is held, they are not new presses
+                if !repeat && context.keys_pressed.insert(*key) {
+                    context.keys_just_pressed.insert(*key);
                 }
-                ElementState::Released => {
-                    match context.keys_pressed.take(&event.physical_key) {
-                        Some(key) => {
-                            context.keys_released.insert(key);
-                        }
-                        _ => (),
-                    };
+                // but they do type text
+                if let Some(text) = text {
+                    context
+                        .text
+                        .extend(text.chars().filter(|c| !c.is_control()));
                 }
+            }
+            InputEvent::KeyReleased(key) => match context.keys_pressed.take(key) {
+                Some(key) => {
+                    context.keys_released.insert(key);
+                }
+                _ => (),
             },
-            WindowEvent::MouseInput { state, button, .. } => match state {
-                ElementState::Pressed => {
-                    context.mouse_pressed.insert(*button);
+            InputEvent::MousePressed(button) => {
+                if context.mouse_pressed.insert(*button) {
+                    context.mouse_just_pressed.insert(*button);
                 }
-                ElementState::Released => match context.mouse_pressed.take(button) {
-                    Some(button) => {
-                        context.mouse_released.insert(button);
-                    }
-                    _ => (),
-                },
+            }
+            InputEvent::MouseReleased(button) => match context.mouse_pressed.take(button) {
+                Some(button) => {
+                    context.mouse_released.insert(button);
+                }
+                _ => (),
             },
-            WindowEvent::CursorMoved { position, .. } => {
+            InputEvent::CursorMoved(position) => {
                 context.mouse_position = *position;
             }
-            WindowEvent::CursorEntered { .. } => {
+            InputEvent::CursorEntered => {
                 context.mouse_within_window = true;
             }
-            WindowEvent::CursorLeft { .. } => {
+            InputEvent::CursorLeft => {
                 context.mouse_within_window = false;
             }
-            _ => (),
+            InputEvent::ScrollLines(x, y) => {
+                context.scroll_lines.0 += x;
+                context.scroll_lines.1 += y;
+            }
+            InputEvent::ScrollPixels(x, y) => {
+                context.scroll_pixels.0 += x;
+                context.scroll_pixels.1 += y;
+            }
+            InputEvent::Touch(touch) => {
+                context.touches.insert(touch.id, *touch);
+            }
+            InputEvent::Modifiers(modifiers) => {
+                context.modifiers = *modifiers;
+            }
+            InputEvent::ImePreedit(text, _) if text.is_empty() => {
+                context.ime_preedit = None;
+            }
+            InputEvent::ImePreedit(text, cursor) => {
+                context.ime_preedit = Some((text.clone(), *cursor));
+            }
+            InputEvent::ImeCommit(text) => {
+                context.ime_preedit = None;
+                context.text.push_str(text);
+            }
+            InputEvent::ImeToggled => {
+                context.ime_preedit = None;
+            }
+            // Several motion events can arrive per frame
+            InputEvent::MouseMotion(x, y) => {
+                context.mouse_travel.0 += x;
+                context.mouse_travel.1 += y;
+            }
+            InputEvent::Gamepad(event) => {
+                Self::gamepad_event_update(context, event);
+            }
         };
     }
 
+    pub fn gamepad_event_update(context: &mut EffectEvent, event: &GamepadEvent) {
+        match event {
+            GamepadEvent::Connected(id, name) => {
+                context
+                    .gamepads
+                    .insert(*id, GamepadState::new(name.clone()));
+                context.gamepads_connected.insert(*id);
+            }
+            GamepadEvent::Disconnected(id) => {
+                if context.gamepads.remove(id).is_some() {
+                    context.gamepads_disconnected.insert(*id);
+                }
+            }
+            GamepadEvent::ButtonPressed(id, button) => {
+                if let Some(gamepad) = context.gamepads.get_mut(id) {
+                    if gamepad.buttons_pressed.insert(*button) {
+                        gamepad.buttons_just_pressed.insert(*button);
+                    }
+                }
+            }
+            GamepadEvent::ButtonReleased(id, button) => {
+                if let Some(gamepad) = context.gamepads.get_mut(id) {
+                    if gamepad.buttons_pressed.remove(button) {
+                        gamepad.buttons_released.insert(*button);
+                    }
+                }
+            }
+            GamepadEvent::AxisChanged(id, axis, value) => {
+                if let Some(gamepad) = context.gamepads.get_mut(id) {
+                    gamepad.set_axis(*axis, *value);
+                }
+            }
+        }
+    }
+
+    pub fn set_dead_zones(context: &mut EffectEvent, dead_zones: GamepadDeadZones) {
+        context.dead_zones = dead_zones;
+    }
+
+    /// Ends the frame for edge triggered input, call once the frame's hooks have run.
     pub fn clear_released(context: &mut EffectEvent) {
+        context.keys_just_pressed.clear();
         context.keys_released.clear();
+        context.mouse_just_pressed.clear();
         context.mouse_released.clear();
+        context.mouse_travel = (0.0, 0.0);
+        context.scroll_lines = (0.0, 0.0);
+        context.scroll_pixels = (0.0, 0.0);
+        context
+            .touches
+            .retain(|_, touch| matches!(touch.phase, TouchPhase::Started | TouchPhase::Moved));
+        context.text.clear();
+        context.close_requested = false;
+        context.gamepads_connected.clear();
+        context.gamepads_disconnected.clear();
+        for gamepad in context.gamepads.values_mut() {
+            gamepad.buttons_just_pressed.clear();
+            gamepad.buttons_released.clear();
+        }
     }
 }
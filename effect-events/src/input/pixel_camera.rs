@@ -0,0 +1,347 @@
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+use super::camera2d::CameraTransform2D;
+
+/// Where a virtual resolution lands in the window when scaled by a whole number,
+/// with the rest of the window left as bars around it. When the window is smaller
+/// than the virtual resolution the offset is negative and the edges are cropped evenly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Letterbox {
+    pub offset: PhysicalPosition<i32>,
+    pub size: PhysicalSize<u32>,
+    pub scale: u32,
+}
+
+impl Letterbox {
+    /// The part of the window inside the letterbox as `(x, y, width, height)`,
+    /// for `RenderPass::set_scissor_rect` so nothing is drawn in the bars.
+    pub fn scissor_rect(&self, window_size: PhysicalSize<u32>) -> (u32, u32, u32, u32) {
+        let (x, width) = Self::clip(self.offset.x, self.size.width, window_size.width);
+        let (y, height) = Self::clip(self.offset.y, self.size.height, window_size.height);
+        (x, y, width, height)
+    }
+
+    /// Start and length of the part of `start..start + len` inside `0..limit`.
+    fn clip(start: i32, len: u32, limit: u32) -> (u32, u32) {
+        let limit = limit as i64;
+        let begin = (start as i64).clamp(0, limit);
+        let end = (start as i64 + len as i64).clamp(begin, limit);
+        (begin as u32, (end - begin) as u32)
+    }
+}
+
+/// Orthographic projection for pixel art. Replaces the perspective projection
+/// from `init_camera`, zooms in whole steps rather than by moving `position.z`,
+/// and keeps one art pixel a whole number of window pixels so sprites don't shimmer.
+pub struct PixelCamera2D {
+    virtual_resolution: PhysicalSize<u32>,
+    pixels_per_unit: f32,
+    zoom: u32,
+    zoom_limits: (u32, u32),
+    snap: bool,
+    letterbox: Letterbox,
+}
+
+impl PixelCamera2D {
+    /// `virtual_resolution` is the size of the game's screen in art pixels, e.g. 320x180,
+    /// and `pixels_per_unit` is how many art pixels make one world unit.
+    pub fn new(virtual_resolution: PhysicalSize<u32>, pixels_per_unit: f32) -> Self {
+        let virtual_resolution = PhysicalSize::new(
+            virtual_resolution.width.max(1),
+            virtual_resolution.height.max(1),
+        );
+        let pixels_per_unit = pixels_per_unit.max(f32::EPSILON);
+        let zoom = 1;
+        let zoom_limits = (1, 8);
+        let snap = true;
+        let letterbox = Letterbox {
+            offset: PhysicalPosition::new(0, 0),
+            size: virtual_resolution,
+            scale: 1,
+        };
+        Self {
+            virtual_resolution,
+            pixels_per_unit,
+            zoom,
+            zoom_limits,
+            snap,
+            letterbox,
+        }
+    }
+
+    pub fn virtual_resolution(&self) -> PhysicalSize<u32> {
+        self.virtual_resolution
+    }
+
+    pub fn zoom(&self) -> u32 {
+        self.zoom
+    }
+
+    /// The letterbox from the last `PixelCameraSystem2D::update`.
+    pub fn letterbox(&self) -> Letterbox {
+        self.letterbox
+    }
+}
+
+pub struct PixelCameraSystem2D;
+
+impl PixelCameraSystem2D {
+    /// Each zoom level doubles, triples etc. the size of an art pixel.
+    pub fn set_zoom(camera: &mut PixelCamera2D, zoom: u32) {
+        camera.zoom = zoom.clamp(camera.zoom_limits.0, camera.zoom_limits.1);
+    }
+
+    pub fn zoom_in(camera: &mut PixelCamera2D) {
+        Self::set_zoom(camera, camera.zoom.saturating_add(1));
+    }
+
+    pub fn zoom_out(camera: &mut PixelCamera2D) {
+        Self::set_zoom(camera, camera.zoom.saturating_sub(1));
+    }
+
+    pub fn set_zoom_limits(camera: &mut PixelCamera2D, min: u32, max: u32) {
+        let min = min.max(1);
+        camera.zoom_limits = (min, max.max(min));
+        Self::set_zoom(camera, camera.zoom);
+    }
+
+    /// Whether the view is moved in whole window pixels. On by default,
+    /// turn it off for smooth sub-pixel scrolling at the cost of shimmer.
+    pub fn set_snap(camera: &mut PixelCamera2D, snap: bool) {
+        camera.snap = snap;
+    }
+
+    /// The largest whole number scale of `virtual_resolution` that fits in the window,
+    /// centred. Never smaller than 1, so tiny windows crop the edges rather than shrink.
+    pub fn letterbox(
+        virtual_resolution: PhysicalSize<u32>,
+        window_size: PhysicalSize<u32>,
+    ) -> Letterbox {
+        let scale_x = window_size.width / virtual_resolution.width.max(1);
+        let scale_y = window_size.height / virtual_resolution.height.max(1);
+        let scale = scale_x.min(scale_y).max(1);
+        let size = PhysicalSize::new(
+            virtual_resolution.width * scale,
+            virtual_resolution.height * scale,
+        );
+        let offset = PhysicalPosition::new(
+            (window_size.width as i64 - size.width as i64).div_euclid(2) as i32,
+            (window_size.height as i64 - size.height as i64).div_euclid(2) as i32,
+        );
+        Letterbox {
+            offset,
+            size,
+            scale,
+        }
+    }
+
+    /// Sets the camera's projection and view for the current window size,
+    /// call each frame after anything that moves the camera.
+    /// The projection only covers the letterbox and is placed inside it, so the
+    /// virtual resolution is drawn at a whole number scale and centred. The renderer
+    /// still clears the whole window, set `Letterbox::scissor_rect` on the render pass
+    /// to keep anything past the edges of the virtual resolution out of the bars.
+    pub fn update<C: CameraTransform2D>(
+        pixel_camera: &mut PixelCamera2D,
+        camera: &mut C,
+        window_size: PhysicalSize<u32>,
+    ) {
+        let letterbox = Self::letterbox(pixel_camera.virtual_resolution, window_size);
+        pixel_camera.letterbox = letterbox;
+        let position = camera.position();
+        let far = position.z.abs() + 1000.0;
+        camera.set_proj(Self::projection(pixel_camera, window_size, far));
+        // Window pixels per world unit
+        let scale = pixel_camera.pixels_per_unit * (pixel_camera.zoom * letterbox.scale) as f32;
+
+        // Only the view is snapped, the position keeps its fractional part so
+        // slow movement still adds up
+        let mut eye = glam::Vec2::new(position.x, position.y);
+        if pixel_camera.snap {
+            eye = (eye * scale).round() / scale;
+        }
+        camera.set_look_at(glam::Mat4::look_at_rh(
+            eye.extend(position.z),
+            eye.extend(0.0),
+            glam::Vec3::Y,
+        ));
+    }
+
+    /// An orthographic projection of the virtual resolution at the current zoom,
+    /// moved and scaled from the whole window onto the letterbox, the same as
+    /// rendering it with a viewport set to the letterbox.
+    fn projection(
+        pixel_camera: &PixelCamera2D,
+        window_size: PhysicalSize<u32>,
+        far: f32,
+    ) -> glam::Mat4 {
+        let letterbox = pixel_camera.letterbox;
+        // World units across the letterbox
+        let scale = pixel_camera.pixels_per_unit * pixel_camera.zoom as f32;
+        let half_width = pixel_camera.virtual_resolution.width as f32 / scale / 2.0;
+        let half_height = pixel_camera.virtual_resolution.height as f32 / scale / 2.0;
+        let ortho = glam::Mat4::orthographic_rh(
+            -half_width,
+            half_width,
+            -half_height,
+            half_height,
+            0.0,
+            far,
+        );
+
+        let window_width = window_size.width.max(1) as f32;
+        let window_height = window_size.height.max(1) as f32;
+        let centre_x = letterbox.offset.x as f32 + letterbox.size.width as f32 / 2.0;
+        let centre_y = letterbox.offset.y as f32 + letterbox.size.height as f32 / 2.0;
+        // Window y grows down, clip space y grows up
+        let to_letterbox = glam::Mat4::from_translation(glam::Vec3::new(
+            centre_x / window_width * 2.0 - 1.0,
+            1.0 - centre_y / window_height * 2.0,
+            0.0,
+        )) * glam::Mat4::from_scale(glam::Vec3::new(
+            letterbox.size.width as f32 / window_width,
+            letterbox.size.height as f32 / window_height,
+            1.0,
+        ));
+        to_letterbox * ortho
+    }
+
+    /// The art pixel under a window pixel, `None` when it is in the bars.
+    /// Uses the same signed offset as the projection, so it also lines up
+    /// when a small window crops the virtual resolution.
+    pub fn screen_to_virtual(
+        pixel_camera: &PixelCamera2D,
+        screen: PhysicalPosition<f64>,
+    ) -> Option<PhysicalPosition<f64>> {
+        let letterbox = pixel_camera.letterbox;
+        let x = (screen.x - letterbox.offset.x as f64) / letterbox.scale as f64;
+        let y = (screen.y - letterbox.offset.y as f64) / letterbox.scale as f64;
+        let size = pixel_camera.virtual_resolution;
+        if x < 0.0 || y < 0.0 || x >= size.width as f64 || y >= size.height as f64 {
+            return None;
+        }
+        Some(PhysicalPosition::new(x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera_in(window_size: PhysicalSize<u32>) -> PixelCamera2D {
+        let mut camera = PixelCamera2D::new(PhysicalSize::new(320, 180), 16.0);
+        camera.letterbox = PixelCameraSystem2D::letterbox(camera.virtual_resolution, window_size);
+        camera
+    }
+
+    fn virtual_at(camera: &PixelCamera2D, x: f64, y: f64) -> Option<PhysicalPosition<f64>> {
+        PixelCameraSystem2D::screen_to_virtual(camera, PhysicalPosition::new(x, y))
+    }
+
+    /// The window pixel a point in view space is drawn at.
+    fn to_window(
+        proj: glam::Mat4,
+        point: glam::Vec2,
+        window_size: PhysicalSize<u32>,
+    ) -> glam::Vec2 {
+        let ndc = proj.project_point3(point.extend(-1.0));
+        glam::Vec2::new(
+            (ndc.x + 1.0) / 2.0 * window_size.width as f32,
+            (1.0 - ndc.y) / 2.0 * window_size.height as f32,
+        )
+    }
+
+    #[test]
+    fn letterbox_scales_by_whole_numbers_and_centres() {
+        let letterbox = PixelCameraSystem2D::letterbox(
+            PhysicalSize::new(320, 180),
+            PhysicalSize::new(1000, 800),
+        );
+        assert_eq!(letterbox.scale, 3);
+        assert_eq!(letterbox.size, PhysicalSize::new(960, 540));
+        assert_eq!(letterbox.offset, PhysicalPosition::new(20, 130));
+    }
+
+    #[test]
+    fn projection_fills_only_the_letterbox() {
+        let window_size = PhysicalSize::new(1000, 800);
+        let camera = camera_in(window_size);
+        let proj = PixelCameraSystem2D::projection(&camera, window_size, 1000.0);
+        // 320x180 art pixels at 16 per unit is 20x11.25 units
+        let top_left = to_window(proj, glam::Vec2::new(-10.0, 5.625), window_size);
+        let bottom_right = to_window(proj, glam::Vec2::new(10.0, -5.625), window_size);
+        assert!(top_left.abs_diff_eq(glam::Vec2::new(20.0, 130.0), 1e-3));
+        assert!(bottom_right.abs_diff_eq(glam::Vec2::new(980.0, 670.0), 1e-3));
+    }
+
+    #[test]
+    fn zoom_shows_less_of_the_world_in_the_same_letterbox() {
+        let window_size = PhysicalSize::new(1000, 800);
+        let mut camera = camera_in(window_size);
+        PixelCameraSystem2D::set_zoom(&mut camera, 2);
+        let proj = PixelCameraSystem2D::projection(&camera, window_size, 1000.0);
+        let top_left = to_window(proj, glam::Vec2::new(-5.0, 2.8125), window_size);
+        assert!(top_left.abs_diff_eq(glam::Vec2::new(20.0, 130.0), 1e-3));
+    }
+
+    #[test]
+    fn scissor_rect_covers_the_letterbox() {
+        let window_size = PhysicalSize::new(1000, 800);
+        let camera = camera_in(window_size);
+        assert_eq!(
+            camera.letterbox().scissor_rect(window_size),
+            (20, 130, 960, 540)
+        );
+    }
+
+    #[test]
+    fn scissor_rect_stays_inside_small_windows() {
+        let window_size = PhysicalSize::new(200, 100);
+        let camera = camera_in(window_size);
+        assert_eq!(camera.letterbox().size, PhysicalSize::new(320, 180));
+        assert_eq!(
+            camera.letterbox().scissor_rect(window_size),
+            (0, 0, 200, 100)
+        );
+    }
+
+    #[test]
+    fn small_windows_crop_the_centre() {
+        let window_size = PhysicalSize::new(200, 100);
+        let camera = camera_in(window_size);
+        assert_eq!(camera.letterbox().scale, 1);
+        assert_eq!(camera.letterbox().offset, PhysicalPosition::new(-60, -40));
+        let proj = PixelCameraSystem2D::projection(&camera, window_size, 1000.0);
+        // The middle of the world stays in the middle of the window
+        let centre = to_window(proj, glam::Vec2::ZERO, window_size);
+        assert!(centre.abs_diff_eq(glam::Vec2::new(100.0, 50.0), 1e-3));
+    }
+
+    #[test]
+    fn screen_to_virtual_skips_the_bars() {
+        let camera = camera_in(PhysicalSize::new(1000, 800));
+        assert_eq!(virtual_at(&camera, 10.0, 400.0), None);
+        assert_eq!(virtual_at(&camera, 500.0, 100.0), None);
+        assert_eq!(
+            virtual_at(&camera, 20.0, 130.0),
+            Some(PhysicalPosition::new(0.0, 0.0))
+        );
+        assert_eq!(
+            virtual_at(&camera, 500.0, 400.0),
+            Some(PhysicalPosition::new(160.0, 90.0))
+        );
+    }
+
+    #[test]
+    fn screen_to_virtual_matches_a_cropped_window() {
+        let camera = camera_in(PhysicalSize::new(200, 100));
+        assert_eq!(
+            virtual_at(&camera, 0.0, 0.0),
+            Some(PhysicalPosition::new(60.0, 40.0))
+        );
+        assert_eq!(
+            virtual_at(&camera, 100.0, 50.0),
+            Some(PhysicalPosition::new(160.0, 90.0))
+        );
+    }
+}
@@ -0,0 +1,451 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use effect_util::effect_error::EffectError;
+use serde::{Deserialize, Serialize};
+
+use super::input_event::InputEvent;
+use super::{EffectEvent, EffectEventSystem};
+
+// Bumped whenever InputEvent or the file layout changes, old recordings are rejected
+const RECORDING_VERSION: u32 = 3;
+// Recording files are the version followed by one chunk per frame, each a little endian
+// length then the frame's time and events, so frames can be appended as they finish
+const CHUNK_LENGTH_BYTES: usize = 4;
+
+/// Input applied to `EffectEvent` over a session, along with each frame's delta time,
+/// so a replay sees the same input on the same frame with the same timing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputRecording {
+    version: u32,
+    frame_times: Vec<Duration>,
+    events: Vec<(u64, InputEvent)>,
+}
+
+impl InputRecording {
+    pub fn new() -> Self {
+        let version = RECORDING_VERSION;
+        let frame_times = Vec::new();
+        let events = Vec::new();
+        Self {
+            version,
+            frame_times,
+            events,
+        }
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frame_times.len() as u64
+    }
+
+    pub fn events(&self) -> &[(u64, InputEvent)] {
+        &self.events
+    }
+}
+
+impl Default for InputRecording {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Plays an `InputRecording` back one frame at a time.
+pub struct InputReplay {
+    recording: InputRecording,
+    frame: u64,
+    next_event: usize,
+}
+
+impl InputReplay {
+    pub fn new(recording: InputRecording) -> Self {
+        let frame = 0;
+        let next_event = 0;
+        Self {
+            recording,
+            frame,
+            next_event,
+        }
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn finished(&self) -> bool {
+        self.frame >= self.recording.frames()
+    }
+}
+
+/// Writes input to a recording file a frame at a time while the game runs, so a
+/// session that panics or is killed still leaves every finished frame on disk.
+pub struct InputRecorder {
+    path: PathBuf,
+    writer: Option<BufWriter<File>>,
+    events: Vec<InputEvent>,
+    frames: u64,
+}
+
+impl InputRecorder {
+    /// The file is created when the first frame is written.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let writer = None;
+        let events = Vec::new();
+        let frames = 0;
+        Self {
+            path,
+            writer,
+            events,
+            frames,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Frames written so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+}
+
+/// Where the main loop takes input from.
+pub enum InputMode {
+    Live,
+    /// Live input, also written to the recorder's file as each frame finishes.
+    Record(InputRecorder),
+    /// Recorded input only, live input is ignored.
+    Replay(InputReplay),
+}
+
+pub struct InputRecordingSystem;
+
+impl InputRecordingSystem {
+    /// Records an event for the frame about to run, call before applying it.
+    pub fn record(recording: &mut InputRecording, event: &InputEvent) {
+        let frame = recording.frames();
+        recording.events.push((frame, event.clone()));
+    }
+
+    /// Closes the current frame, later events belong to the next one.
+    pub fn end_frame(recording: &mut InputRecording, frame_time: Duration) {
+        recording.frame_times.push(frame_time);
+    }
+
+    pub fn save(recording: &InputRecording, path: impl AsRef<Path>) -> Result<()> {
+        let mut writer = Self::create(path.as_ref(), recording.version)?;
+        let mut events = recording.events.iter().peekable();
+        for (frame, frame_time) in recording.frame_times.iter().enumerate() {
+            let mut frame_events = Vec::new();
+            while let Some((_, event)) =
+                events.next_if(|(event_frame, _)| *event_frame == frame as u64)
+            {
+                frame_events.push(event.clone());
+            }
+            Self::write_frame(&mut writer, *frame_time, &frame_events)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// A frame cut off part way through, as left by a crash while it was being
+    /// written, is dropped and the frames before it are kept.
+    pub fn load(path: impl AsRef<Path>) -> Result<InputRecording> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        let invalid = |e: &dyn std::fmt::Display| {
+            EffectError::new(&format!("Invalid input recording {}: {e}", path.display()))
+        };
+        let version_bytes = bytes.get(..4).ok_or_else(|| invalid(&"missing version"))?;
+        let version: u32 = bincode::deserialize(version_bytes).map_err(|e| invalid(&e))?;
+        if version != RECORDING_VERSION {
+            return Err(EffectError::new(&format!(
+                "Input recording {} is version {version}, expected {RECORDING_VERSION}",
+                path.display(),
+            ))
+            .into());
+        }
+        let mut recording = InputRecording::new();
+        let mut rest = &bytes[4..];
+        while rest.len() >= CHUNK_LENGTH_BYTES {
+            let (length, chunk) = rest.split_at(CHUNK_LENGTH_BYTES);
+            let length = u32::from_le_bytes(length.try_into().unwrap()) as usize;
+            if chunk.len() < length {
+                break;
+            }
+            let (chunk, next) = chunk.split_at(length);
+            let (frame_time, events): (Duration, Vec<InputEvent>) =
+                bincode::deserialize(chunk).map_err(|e| invalid(&e))?;
+            for event in events {
+                Self::record(&mut recording, &event);
+            }
+            Self::end_frame(&mut recording, frame_time);
+            rest = next;
+        }
+        Ok(recording)
+    }
+
+    fn create(path: &Path, version: u32) -> Result<BufWriter<File>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&bincode::serialize(&version)?)?;
+        Ok(writer)
+    }
+
+    fn write_frame(
+        writer: &mut impl Write,
+        frame_time: Duration,
+        events: &[InputEvent],
+    ) -> Result<()> {
+        let chunk = bincode::serialize(&(frame_time, events))?;
+        let length = u32::try_from(chunk.len())
+            .map_err(|_| EffectError::new("Too much input in one frame to record"))?;
+        writer.write_all(&length.to_le_bytes())?;
+        writer.write_all(&chunk)?;
+        Ok(())
+    }
+
+    /// Writes the frame's events and time to the recorder's file and flushes it.
+    pub fn write_recorder_frame(recorder: &mut InputRecorder, frame_time: Duration) -> Result<()> {
+        let writer = match recorder.writer.as_mut() {
+            Some(writer) => writer,
+            None => recorder
+                .writer
+                .insert(Self::create(&recorder.path, RECORDING_VERSION)?),
+        };
+        Self::write_frame(writer, frame_time, &recorder.events)?;
+        writer.flush()?;
+        recorder.events.clear();
+        recorder.frames += 1;
+        Ok(())
+    }
+
+    /// Applies live input according to the mode.
+    pub fn live_input(mode: &mut InputMode, context: &mut EffectEvent, event: InputEvent) {
+        match mode {
+            InputMode::Live => EffectEventSystem::input_event_update(context, &event),
+            InputMode::Record(recorder) => {
+                recorder.events.push(event.clone());
+                EffectEventSystem::input_event_update(context, &event);
+            }
+            InputMode::Replay(_) => (),
+        }
+    }
+
+    /// Call once per frame before the app's hooks. Returns the frame time to use,
+    /// the measured one unless replaying, or `None` once a replay has run out.
+    pub fn begin_frame(
+        mode: &mut InputMode,
+        context: &mut EffectEvent,
+        frame_time: Duration,
+    ) -> Option<Duration> {
+        match mode {
+            InputMode::Replay(replay) => Self::replay_frame(replay, context),
+            _ => Some(frame_time),
+        }
+    }
+
+    /// Call once per frame after the app's hooks, with the frame time `begin_frame` returned.
+    /// When recording, the frame is written to disk before this returns.
+    pub fn finish_frame(mode: &mut InputMode, frame_time: Duration) -> Result<()> {
+        match mode {
+            InputMode::Record(recorder) => Self::write_recorder_frame(recorder, frame_time),
+            _ => Ok(()),
+        }
+    }
+
+    /// Call once the loop has stopped. Makes sure the file exists even if no frame
+    /// finished, input from a frame that didn't finish is not written.
+    pub fn finish(mode: &mut InputMode) -> Result<()> {
+        let InputMode::Record(recorder) = mode else {
+            return Ok(());
+        };
+        match recorder.writer.as_mut() {
+            Some(writer) => writer.flush()?,
+            None => {
+                let mut writer = Self::create(&recorder.path, RECORDING_VERSION)?;
+                writer.flush()?;
+                recorder.writer = Some(writer);
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the next frame's events to `context` and returns the frame time to use,
+    /// or `None` once the recording has run out.
+    pub fn replay_frame(replay: &mut InputReplay, context: &mut EffectEvent) -> Option<Duration> {
+        let frame_time = *replay.recording.frame_times.get(replay.frame as usize)?;
+        while let Some((frame, event)) = replay.recording.events.get(replay.next_event) {
+            if *frame > replay.frame {
+                break;
+            }
+            EffectEventSystem::input_event_update(context, event);
+            replay.next_event += 1;
+        }
+        replay.frame += 1;
+        Some(frame_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use winit::dpi::PhysicalSize;
+
+    use super::*;
+    use crate::input::gamepad::{GamepadButton, GamepadEvent, GamepadID};
+
+    const PAD: GamepadID = GamepadID(0);
+
+    // Unique per test and process so tests can run in parallel
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("effect-record-{}-{name}.bin", std::process::id()))
+    }
+
+    fn sample_recording() -> InputRecording {
+        let mut recording = InputRecording::new();
+        let frame_time = Duration::from_millis(16);
+        InputRecordingSystem::record(
+            &mut recording,
+            &InputEvent::Resized(PhysicalSize::new(640, 480)),
+        );
+        InputRecordingSystem::record(
+            &mut recording,
+            &InputEvent::Gamepad(GamepadEvent::Connected(PAD, String::from("Pad"))),
+        );
+        InputRecordingSystem::end_frame(&mut recording, frame_time);
+        InputRecordingSystem::end_frame(&mut recording, Duration::from_millis(33));
+        InputRecordingSystem::record(
+            &mut recording,
+            &InputEvent::Gamepad(GamepadEvent::ButtonPressed(PAD, GamepadButton::South)),
+        );
+        InputRecordingSystem::end_frame(&mut recording, frame_time);
+        recording
+    }
+
+    #[test]
+    fn events_belong_to_the_frame_they_were_recorded_in() {
+        let recording = sample_recording();
+        assert_eq!(recording.frames(), 3);
+        let frames: Vec<u64> = recording.events().iter().map(|(frame, _)| *frame).collect();
+        assert_eq!(frames, vec![0, 0, 2]);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let path = temp_path("round-trip");
+        let recording = sample_recording();
+        InputRecordingSystem::save(&recording, &path).unwrap();
+        let loaded = InputRecordingSystem::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, recording);
+    }
+
+    #[test]
+    fn load_rejects_other_versions() {
+        let path = temp_path("version");
+        let mut recording = sample_recording();
+        recording.version = RECORDING_VERSION + 1;
+        InputRecordingSystem::save(&recording, &path).unwrap();
+        let error = InputRecordingSystem::load(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(error.to_string().contains("version"), "{error}");
+    }
+
+    #[test]
+    fn load_rejects_invalid_files() {
+        let path = temp_path("invalid");
+        fs::write(&path, b"not a recording").unwrap();
+        let result = InputRecordingSystem::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+        assert!(InputRecordingSystem::load(temp_path("missing")).is_err());
+    }
+
+    #[test]
+    fn recorder_writes_each_frame_as_it_finishes() {
+        let path = temp_path("stream");
+        let mut mode = InputMode::Record(InputRecorder::new(&path));
+        let mut context = EffectEvent::new();
+        let connected = InputEvent::Gamepad(GamepadEvent::Connected(PAD, String::from("Pad")));
+        InputRecordingSystem::live_input(&mut mode, &mut context, connected.clone());
+        InputRecordingSystem::finish_frame(&mut mode, Duration::from_millis(16)).unwrap();
+        InputRecordingSystem::finish_frame(&mut mode, Duration::from_millis(17)).unwrap();
+        // Input for a frame that never finishes, as when the game crashes part way
+        let pressed = InputEvent::Gamepad(GamepadEvent::ButtonPressed(PAD, GamepadButton::South));
+        InputRecordingSystem::live_input(&mut mode, &mut context, pressed);
+
+        // Read while the recorder is still open, without finish or drop
+        let loaded = InputRecordingSystem::load(&path).unwrap();
+        std::mem::forget(mode);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.frames(), 2);
+        assert_eq!(loaded.events(), &[(0, connected)]);
+    }
+
+    #[test]
+    fn load_drops_a_frame_cut_off_part_way() {
+        let path = temp_path("truncated");
+        let recording = sample_recording();
+        InputRecordingSystem::save(&recording, &path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+        let loaded = InputRecordingSystem::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.frames(), 2);
+        assert_eq!(loaded.events(), &recording.events()[..2]);
+    }
+
+    #[test]
+    fn finish_creates_the_file_without_frames() {
+        let path = temp_path("empty");
+        let mut mode = InputMode::Record(InputRecorder::new(&path));
+        InputRecordingSystem::finish(&mut mode).unwrap();
+        let loaded = InputRecordingSystem::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, InputRecording::new());
+    }
+
+    #[test]
+    fn replay_applies_each_frame_with_its_recorded_time() {
+        let mut replay = InputReplay::new(sample_recording());
+        let mut context = EffectEvent::new();
+
+        let frame_time = InputRecordingSystem::replay_frame(&mut replay, &mut context);
+        assert_eq!(frame_time, Some(Duration::from_millis(16)));
+        assert_eq!(context.window_size(), PhysicalSize::new(640, 480));
+        assert!(context.is_gamepad_connected(PAD));
+        EffectEventSystem::clear_released(&mut context);
+
+        let frame_time = InputRecordingSystem::replay_frame(&mut replay, &mut context);
+        assert_eq!(frame_time, Some(Duration::from_millis(33)));
+        assert!(!context.is_gamepad_button_pressed(PAD, GamepadButton::South));
+
+        InputRecordingSystem::replay_frame(&mut replay, &mut context);
+        assert!(context.is_gamepad_button_just_pressed(PAD, GamepadButton::South));
+        assert!(replay.finished());
+        assert_eq!(
+            InputRecordingSystem::replay_frame(&mut replay, &mut context),
+            None
+        );
+    }
+
+    #[test]
+    fn replay_ignores_live_input() {
+        let mut mode = InputMode::Replay(InputReplay::new(sample_recording()));
+        let mut context = EffectEvent::new();
+        InputRecordingSystem::live_input(
+            &mut mode,
+            &mut context,
+            InputEvent::Gamepad(GamepadEvent::Connected(GamepadID(1), String::from("Live"))),
+        );
+        assert!(!context.is_gamepad_connected(GamepadID(1)));
+    }
+}
@@ -1,21 +1,33 @@
-use effect_engine::core::camera::camera2d::{Camera2D, Camera2DSystem, CameraAction};
+use std::path::Path;
+use std::time::Duration;
+
+use effect_engine::app::EffectApp;
+use effect_engine::args::{EffectArgs, EffectArgsSystem};
+use effect_engine::backend::EffectBackend2D;
+use effect_engine::control::EffectControl;
+use effect_engine::core::camera::camera2d::CameraAction;
 use effect_engine::core::id::{LayerID, TextureID};
 use effect_engine::core::misc::fullscreen::FullScreenMode;
 use effect_engine::core::primitives::vector::Vector3;
 use effect_engine::events::input::camera2d::CameraUpdateSystem2D;
-use effect_engine::web_render::app::effect2d::EffectEngine2D;
+use effect_engine::events::input::input_map::{Binding, InputMap};
+use effect_engine::events::input::EffectEvent;
+use effect_engine::settings::EffectSettingsSystem;
 use effect_engine::web_render::texture::texture2d::Texture2D;
-use effect_engine::EffectAppBuilder;
+use effect_engine::{EffectAppBuilder, EffectAppVariant};
 use winit::dpi::PhysicalSize;
 use winit::keyboard::KeyCode;
 
-struct GameState {
-    initialised: bool,
-    camera: Option<Camera2D>,
+// Frames a headless run lasts, as nothing closes it
+const HEADLESS_FRAMES: u64 = 600;
+
+// Written against EffectBackend2D so the same game runs in a window or with --headless
+struct GameState<C> {
+    camera: Option<C>,
 }
 
-impl GameState {
-    pub fn initialise(&mut self, app: &mut EffectEngine2D) {
+impl<E: EffectBackend2D> EffectApp<E> for GameState<E::Camera> {
+    fn init(&mut self, app: &mut E, _control: &mut EffectControl) {
         let tex_id = TextureID("Tree");
         let texture = Texture2D::new(tex_id, "assets/tree.png");
         let tex = vec![texture];
@@ -23,58 +35,71 @@ impl GameState {
             .unwrap();
         let ent = app.init_entity(Vector3::new(0.0, 0.0, -1.0), LayerID(0), tex_id);
         let ents = vec![&ent];
-        app.set_entities(LayerID(0), &ents);
+        app.set_entities(LayerID(0), &ents).unwrap();
         let mut camera = app.init_camera(90.0);
-        Camera2DSystem::set_inputs(
-            &mut camera,
-            &[
-                (CameraAction::Left, KeyCode::KeyA),
-                (CameraAction::Right, KeyCode::KeyD),
-            ],
-        );
-        Camera2DSystem::set_speed(&mut camera, 0.02);
+        // Player rebinds from bindings.toml replace the defaults set here
+        let mut camera_bindings = InputMap::new();
+        camera_bindings.bind(CameraAction::Left, Binding::Key(KeyCode::KeyA));
+        camera_bindings.bind(CameraAction::Right, Binding::Key(KeyCode::KeyD));
+        camera_bindings.store_defaults();
+        if Path::new("bindings.toml").is_file() {
+            CameraUpdateSystem2D::load_bindings(&mut camera_bindings, "bindings.toml").unwrap();
+        }
+        app.set_camera_bindings(&mut camera, &camera_bindings);
+        app.set_camera_speed(&mut camera, 0.02);
         self.camera = Some(camera);
-        self.initialised = true;
     }
 
-    pub fn initialised(&self) -> bool {
-        self.initialised
+    fn update(
+        &mut self,
+        ctx: &mut EffectEvent,
+        delta_time: Duration,
+        _control: &mut EffectControl,
+        app: &mut E,
+    ) {
+        // proves the failure is only for the camera
+        if ctx.is_key_just_pressed(KeyCode::Comma) {
+            println!("Hi");
+        }
+
+        if let Some(camera) = self.camera.as_mut() {
+            app.update_camera(camera, ctx, delta_time);
+        }
     }
 }
 
-// TODO: Simplifiy initialisation of parts.
 // TODO: Reduce dependency on app for initialisation,
 // so user can do their init first
 fn main() {
-    let event_loop = EffectAppBuilder::default()
+    // Players and QA can override these through settings.toml or the command line,
+    // e.g. --windowed --resolution 1920x1080 or --set vsync=true
+    let mut args = EffectArgs::new();
+    EffectArgsSystem::parse_env(&mut args);
+    let settings =
+        EffectSettingsSystem::load_layered("settings.toml", "effect-examples", Vec::new()).unwrap();
+    let app = EffectAppBuilder::default()
         .fullscreen_mode(FullScreenMode::BORDERLESS)
         .resolution(1280, 720)
         .vsync(false)
+        .target_fps(Some(144))
+        .background_fps(Some(15))
         .monitor(0)
-        .build()
-        .get_wgpu_2d();
+        .settings(&settings)
+        .args(&args)
+        .build();
 
     // Extremely verbose just to get a texture on screen.
     // This will be improved when layer is internalised and further improved through the
     // the user of builders and code cleanup
-    let mut game = GameState {
-        initialised: false,
-        camera: None,
-    };
-    event_loop.run(|ctx, _delta_time, control, app| {
-        if ctx.close_requested() {
-            control.exit();
-        }
-        if !game.initialised() {
-            game.initialise(app);
+    match app {
+        EffectAppVariant::Web2D(event_loop) => {
+            event_loop.run(GameState { camera: None }).unwrap();
         }
-        // proves the failure is only for the camera
-        if ctx.is_key_pressed(KeyCode::Comma) {
-            println!("Hi");
+        EffectAppVariant::Headless(headless_loop) => {
+            headless_loop
+                .frame_limit(HEADLESS_FRAMES)
+                .run(GameState { camera: None })
+                .unwrap();
         }
-
-        app.render().unwrap();
-        app.update_camera(game.camera.as_mut().unwrap(), &ctx, _delta_time);
-        app.update(ctx);
-    });
+    }
 }